@@ -1,104 +1,778 @@
-use std::collections::HashSet;
-use std::convert::From;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
 
 static INPUT: &str = include_str!("../input.txt");
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Register {
+    Acc,
+    A,
+    B,
+    C,
+    D,
+}
+
+impl Register {
+    const COUNT: usize = 5;
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl FromStr for Register {
+    type Err = ParseErrorKind;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        use Register::*;
+
+        match string {
+            "acc" => Ok(Acc),
+            "a" => Ok(A),
+            "b" => Ok(B),
+            "c" => Ok(C),
+            "d" => Ok(D),
+            _ => Err(ParseErrorKind::InvalidRegister(string.to_string())),
+        }
+    }
+}
+
+// The first three variants are the original boot code instruction set
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 enum Instruction {
     Nop(isize),
     Acc(isize),
     Jmp(isize),
+    Add(Register, isize),
+    Jz(Register, isize),
+    Jnz(Register, isize),
+    Out(Register),
+    Hlt,
 }
 
 impl Instruction {
-    fn flop(self) -> Self {
+    fn flop(self) -> Option<Self> {
         use Instruction::*;
 
         match self {
-            Nop(offset) => Jmp(offset),
-            Jmp(offset) => Nop(offset),
-            Acc(_) => {
-                eprintln!("can't flop Acc");
-                panic!();
-            } // x => x,
+            Nop(offset) => Some(Jmp(offset)),
+            Jmp(offset) => Some(Nop(offset)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ParseErrorKind {
+    UnknownOpcode(String),
+    InvalidOperand(String),
+    InvalidRegister(String),
+    MissingOperand,
+    TrailingOperand(String),
+    InvalidLabel(String),
+    DuplicateLabel(String),
+    UnknownLabel(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ParseError {
+    line: usize,
+    kind: ParseErrorKind,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ParseErrorKind::*;
+
+        write!(f, "line {}: ", self.line)?;
+
+        match &self.kind {
+            UnknownOpcode(opcode) => write!(f, "unknown opcode: {}", opcode),
+            InvalidOperand(operand) => write!(f, "invalid operand: {}", operand),
+            InvalidRegister(register) => write!(f, "invalid register: {}", register),
+            MissingOperand => write!(f, "missing operand"),
+            TrailingOperand(operand) => write!(f, "unexpected operand: {}", operand),
+            InvalidLabel(label) => write!(f, "invalid label: {}", label),
+            DuplicateLabel(label) => write!(f, "duplicate label: {}", label),
+            UnknownLabel(label) => write!(f, "unknown label: {}", label),
+        }
+    }
+}
+
+type Labels<'a> = HashMap<&'a str, usize>;
+
+fn is_label(word: &str) -> bool {
+    let mut chars = word.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// Offsets are either literal (+3, -2) or a label, which is resolved relative
+// to the instruction at index
+fn parse_offset(
+    operand: Option<&str>,
+    index: usize,
+    labels: &Labels,
+) -> Result<isize, ParseErrorKind> {
+    let operand = operand.ok_or(ParseErrorKind::MissingOperand)?;
+
+    if is_label(operand) {
+        return match labels.get(operand) {
+            Some(&target) => Ok(target as isize - index as isize),
+            None => Err(ParseErrorKind::UnknownLabel(operand.to_string())),
+        };
+    }
+
+    operand
+        .parse()
+        .map_err(|_| ParseErrorKind::InvalidOperand(operand.to_string()))
+}
+
+fn parse_value(operand: Option<&str>) -> Result<isize, ParseErrorKind> {
+    let operand = operand.ok_or(ParseErrorKind::MissingOperand)?;
+    operand
+        .parse()
+        .map_err(|_| ParseErrorKind::InvalidOperand(operand.to_string()))
+}
+
+fn parse_register(operand: Option<&str>) -> Result<Register, ParseErrorKind> {
+    operand.ok_or(ParseErrorKind::MissingOperand)?.parse()
+}
+
+impl Instruction {
+    fn parse(string: &str, index: usize, labels: &Labels) -> Result<Self, ParseErrorKind> {
+        use Instruction::*;
+
+        let mut words = string.split_whitespace();
+        let opcode = words.next().unwrap_or_default();
+
+        let instruction = match opcode {
+            "nop" => Nop(parse_offset(words.next(), index, labels)?),
+            "acc" => Acc(parse_value(words.next())?),
+            "jmp" => Jmp(parse_offset(words.next(), index, labels)?),
+            "add" => Add(parse_register(words.next())?, parse_value(words.next())?),
+            "jz" => Jz(
+                parse_register(words.next())?,
+                parse_offset(words.next(), index, labels)?,
+            ),
+            "jnz" => Jnz(
+                parse_register(words.next())?,
+                parse_offset(words.next(), index, labels)?,
+            ),
+            "out" => Out(parse_register(words.next())?),
+            "hlt" => Hlt,
+            _ => return Err(ParseErrorKind::UnknownOpcode(opcode.to_string())),
+        };
+
+        match words.next() {
+            Some(operand) => Err(ParseErrorKind::TrailingOperand(operand.to_string())),
+            None => Ok(instruction),
         }
     }
 }
 
-impl From<&str> for Instruction {
-    fn from(string: &str) -> Self {
-        let opcode = &string[..3];
-        let offset = string[4..].parse().unwrap();
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Register::*;
 
-        match opcode {
-            "nop" => Self::Nop(offset),
-            "acc" => Self::Acc(offset),
-            "jmp" => Self::Jmp(offset),
-            _ => {
-                eprintln!("invalid opcode: {}", opcode);
-                panic!();
+        let name = match self {
+            Acc => "acc",
+            A => "a",
+            B => "b",
+            C => "c",
+            D => "d",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Instruction::*;
+
+        match self {
+            Nop(offset) => write!(f, "nop {:+}", offset),
+            Acc(value) => write!(f, "acc {:+}", value),
+            Jmp(offset) => write!(f, "jmp {:+}", offset),
+            Add(register, value) => write!(f, "add {} {:+}", register, value),
+            Jz(register, offset) => write!(f, "jz {} {:+}", register, offset),
+            Jnz(register, offset) => write!(f, "jnz {} {:+}", register, offset),
+            Out(register) => write!(f, "out {}", register),
+            Hlt => write!(f, "hlt"),
+        }
+    }
+}
+
+// Source lines are `[label:] [instruction] [; comment]`. Labels are collected
+// in a first pass so that jumps can refer to labels further down.
+fn parse_program(source: &str) -> Result<Vec<Instruction>, ParseError> {
+    let mut labels: Labels = Default::default();
+    let mut statements: Vec<(usize, &str)> = vec![];
+
+    for (i, line) in source.lines().enumerate() {
+        let mut line = line.split(';').next().unwrap().trim();
+
+        if let Some(colon) = line.find(':') {
+            let label = line[..colon].trim();
+            if !is_label(label) {
+                let kind = ParseErrorKind::InvalidLabel(label.to_string());
+                return Err(ParseError { line: i + 1, kind });
             }
+
+            if labels.insert(label, statements.len()).is_some() {
+                let kind = ParseErrorKind::DuplicateLabel(label.to_string());
+                return Err(ParseError { line: i + 1, kind });
+            }
+
+            line = line[colon + 1..].trim();
+        }
+
+        if !line.is_empty() {
+            statements.push((i + 1, line));
         }
     }
+
+    statements
+        .iter()
+        .enumerate()
+        .map(|(index, &(line, statement))| {
+            Instruction::parse(statement, index, &labels).map_err(|kind| ParseError { line, kind })
+        })
+        .collect()
+}
+
+fn disassemble(code: &[Instruction]) -> String {
+    code.iter()
+        .map(|instruction| format!("{}\n", instruction))
+        .collect()
+}
+
+#[derive(Debug, Default)]
+struct Machine {
+    pc: isize,
+    registers: [isize; Register::COUNT],
+    output: Vec<isize>,
 }
 
+impl Machine {
+    fn acc(&self) -> isize {
+        self.registers[Register::Acc.index()]
+    }
+
+    // Returns false if the instruction halted the machine
+    fn step(&mut self, instruction: Instruction) -> bool {
+        use Instruction::*;
+
+        match instruction {
+            Nop(_) => (),
+            Acc(offset) => self.registers[Register::Acc.index()] += offset,
+            Jmp(offset) => self.pc += offset - 1,
+            Add(register, value) => self.registers[register.index()] += value,
+            Jz(register, offset) => {
+                if self.registers[register.index()] == 0 {
+                    self.pc += offset - 1;
+                }
+            }
+            Jnz(register, offset) => {
+                if self.registers[register.index()] != 0 {
+                    self.pc += offset - 1;
+                }
+            }
+            Out(register) => self.output.push(self.registers[register.index()]),
+            Hlt => return false,
+        }
+
+        self.pc += 1;
+        true
+    }
+}
+
+enum Mode {
+    Run,
+    Disassemble,
+    Dot,
+}
+
+// usage: day-08 [--disassemble | --dot] [--max-steps N] [program.txt]
 fn main() {
-    let code: Vec<Instruction> = INPUT.lines().map(Instruction::from).collect();
+    let mut mode = Mode::Run;
+    let mut path = None;
+    let mut max_steps = None;
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--disassemble" => mode = Mode::Disassemble,
+            "--dot" => mode = Mode::Dot,
+            "--max-steps" => match args.next().map(|steps| steps.parse()) {
+                Some(Ok(steps)) => max_steps = Some(steps),
+                _ => {
+                    eprintln!("--max-steps needs a number");
+                    std::process::exit(1);
+                }
+            },
+            _ => path = Some(arg),
+        }
+    }
+
+    let source = match path {
+        Some(path) => std::fs::read_to_string(path).unwrap(),
+        None => INPUT.to_string(),
+    };
 
-    part1(&code);
-    part2(code);
+    let code = match parse_program(&source) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    match mode {
+        Mode::Run => {
+            part1(&code, max_steps);
+            part2(&code, max_steps);
+        }
+        Mode::Disassemble => print!("{}", disassemble(&code)),
+        Mode::Dot => print!("{}", to_dot(&code)),
+    }
 }
 
-fn part1(code: &[Instruction]) {
-    let (_, acc) = run_program(code);
-    println!("part1: {}", acc);
+fn part1(code: &[Instruction], max_steps: Option<usize>) {
+    let (halt, machine) = run_program(code, max_steps);
+
+    for value in machine.output.iter() {
+        println!("out: {}", value);
+    }
+
+    println!("part1: {} ({})", machine.acc(), halt);
 }
 
-// return type: (terminated?, acc_at_end)
-fn run_program(code: &[Instruction]) -> (bool, isize) {
-    use Instruction::*;
+// Control flow only depends on the pc and the registers tested by conditional
+// jumps, so revisiting the same combination of those means we're looping
+fn conditional_registers(code: &[Instruction]) -> Vec<Register> {
+    let mut registers = vec![];
+
+    for instruction in code {
+        if let Instruction::Jz(register, _) | Instruction::Jnz(register, _) = *instruction {
+            if !registers.contains(&register) {
+                registers.push(register);
+            }
+        }
+    }
+
+    registers
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Halt {
+    Terminated,
+    InfiniteLoop,
+    OutOfBounds(isize),
+    BudgetExhausted,
+}
+
+impl fmt::Display for Halt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Halt::*;
+
+        match self {
+            Terminated => write!(f, "terminated"),
+            InfiniteLoop => write!(f, "infinite loop detected"),
+            OutOfBounds(target) => write!(f, "jumped out of bounds to {}", target),
+            BudgetExhausted => write!(f, "step budget exhausted"),
+        }
+    }
+}
 
-    let mut pc: isize = 0;
-    let mut acc: isize = 0;
-    let mut visited_instructions: HashSet<usize> = Default::default();
+// max_steps of None means the program can run for as long as it likes
+fn run_program(code: &[Instruction], max_steps: Option<usize>) -> (Halt, Machine) {
+    trace_program(code, max_steps, |_| ())
+}
+
+// Like run_program but calls on_step with the pc of every executed instruction
+fn trace_program(
+    code: &[Instruction],
+    max_steps: Option<usize>,
+    mut on_step: impl FnMut(usize),
+) -> (Halt, Machine) {
+    let mut machine = Machine::default();
+    let watched = conditional_registers(code);
+    let mut visited_states: HashSet<(usize, Vec<isize>)> = Default::default();
+    let mut steps = 0;
 
     loop {
-        if visited_instructions.contains(&(pc as usize)) {
-            return (false, acc);
+        if machine.pc < 0 || machine.pc as usize > code.len() {
+            return (Halt::OutOfBounds(machine.pc), machine);
+        }
+
+        let pc = machine.pc as usize;
+        let state = (
+            pc,
+            watched
+                .iter()
+                .map(|register| machine.registers[register.index()])
+                .collect(),
+        );
+
+        if !visited_states.insert(state) {
+            return (Halt::InfiniteLoop, machine);
+        }
+
+        if pc == code.len() {
+            return (Halt::Terminated, machine);
+        }
+
+        if max_steps == Some(steps) {
+            return (Halt::BudgetExhausted, machine);
+        }
+
+        steps += 1;
+        on_step(pc);
+        if !machine.step(code[pc]) {
+            return (Halt::Terminated, machine);
+        }
+    }
+}
+
+fn part2(code: &[Instruction], max_steps: Option<usize>) {
+    match repair(code, max_steps) {
+        Some(repair) => println!(
+            "part2: ({}, {}) [{:?} => {:?}]",
+            repair.index, repair.acc, repair.original, repair.patched
+        ),
+        None if run_program(code, max_steps).0 == Halt::Terminated => {
+            println!("part2: the program already terminates")
+        }
+        None => println!("part2: no single flip terminates the program"),
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Repair {
+    index: usize,
+    original: Instruction,
+    patched: Instruction,
+    acc: isize,
+}
+
+// Static successors of the instruction at index. Index code.len() stands for
+// termination; jumps that leave the program entirely have no successor.
+fn successors(code: &[Instruction], index: usize) -> Vec<usize> {
+    use Instruction::*;
+
+    let jump = |offset: isize| {
+        let target = index as isize + offset;
+        if target >= 0 && target as usize <= code.len() {
+            Some(target as usize)
         } else {
-            visited_instructions.insert(pc as usize);
+            None
         }
+    };
+
+    match code[index] {
+        Jmp(offset) => jump(offset).into_iter().collect(),
+        Jz(_, offset) | Jnz(_, offset) => jump(1).into_iter().chain(jump(offset)).collect(),
+        Hlt => vec![code.len()],
+        _ => jump(1).into_iter().collect(),
+    }
+}
 
-        if pc as usize == code.len() {
-            return (true, acc);
+// Every instruction from which termination is reachable in the unpatched program
+fn reaches_termination(code: &[Instruction]) -> Vec<bool> {
+    let mut predecessors: Vec<Vec<usize>> = vec![vec![]; code.len() + 1];
+    for index in 0..code.len() {
+        for successor in successors(code, index) {
+            predecessors[successor].push(index);
         }
+    }
 
-        visited_instructions.insert(pc as usize);
+    let mut reachable = vec![false; code.len() + 1];
+    reachable[code.len()] = true;
+    let mut stack = vec![code.len()];
 
-        let instruction = code.get(pc as usize).unwrap();
-        match instruction {
-            Nop(_) => (),
-            Acc(offset) => acc += offset,
-            Jmp(offset) => pc += offset - 1,
+    while let Some(index) = stack.pop() {
+        for &predecessor in predecessors[index].iter() {
+            if !reachable[predecessor] {
+                reachable[predecessor] = true;
+                stack.push(predecessor);
+            }
+        }
+    }
+
+    reachable
+}
+
+// Splits the program into maximal straight-line runs: a block starts at the
+// entry point, at every jump target and right after every jump or halt
+fn basic_blocks(code: &[Instruction]) -> Vec<Range<usize>> {
+    use Instruction::*;
+
+    let mut leaders = vec![false; code.len() + 1];
+    leaders[0] = true;
+
+    for (index, instruction) in code.iter().enumerate() {
+        if let Jmp(_) | Jz(_, _) | Jnz(_, _) | Hlt = instruction {
+            leaders[index + 1] = true;
+            for successor in successors(code, index) {
+                leaders[successor] = true;
+            }
         }
+    }
+
+    leaders[code.len()] = true;
 
-        pc += 1;
+    let mut blocks = vec![];
+    let mut start = 0;
+    for (index, &leader) in leaders.iter().enumerate().skip(1) {
+        if leader {
+            blocks.push(start..index);
+            start = index;
+        }
     }
+
+    blocks
 }
 
-fn part2(mut code: Vec<Instruction>) {
-    for i in 0..code.len() {
-        if let Instruction::Acc(_) = code[i] {
+fn to_dot(code: &[Instruction]) -> String {
+    use Instruction::*;
+
+    let blocks = basic_blocks(code);
+    let block_of = |index: usize| match blocks.iter().position(|block| block.contains(&index)) {
+        Some(block) => format!("b{}", block),
+        None => "end".to_string(),
+    };
+
+    let mut dot = String::from("digraph program {\n    node [shape=box, fontname=monospace];\n");
+    dot.push_str("    end [shape=doublecircle];\n");
+
+    for (i, block) in blocks.iter().enumerate() {
+        let label: String = block
+            .clone()
+            .map(|index| format!("{}: {}\\l", index, code[index]))
+            .collect();
+        dot.push_str(&format!("    b{} [label=\"{}\"];\n", i, label));
+    }
+
+    for (i, block) in blocks.iter().enumerate() {
+        let last = block.end - 1;
+        let target = |offset: isize| last as isize + offset;
+        let mut edges: Vec<(isize, &str)> = vec![];
+
+        match code[last] {
+            Jmp(offset) => edges.push((target(offset), "jmp")),
+            Jz(_, offset) => {
+                edges.push((target(1), "nonzero"));
+                edges.push((target(offset), "zero"));
+            }
+            Jnz(_, offset) => {
+                edges.push((target(1), "zero"));
+                edges.push((target(offset), "nonzero"));
+            }
+            Hlt => edges.push((code.len() as isize, "hlt")),
+            _ => edges.push((target(1), "")),
+        }
+
+        for (target, label) in edges {
+            // Quoted since negative targets aren't valid bare IDs
+            let node = if target < 0 || target as usize > code.len() {
+                let node = format!("\"oob{}\"", target);
+                dot.push_str(&format!(
+                    "    {} [label=\"{:+}\", shape=octagon];\n",
+                    node, target
+                ));
+                node
+            } else {
+                block_of(target as usize)
+            };
+
+            dot.push_str(&format!("    b{} -> {} [label=\"{}\"];\n", i, node, label));
+        }
+    }
+
+    dot.push_str("}\n");
+    dot
+}
+
+// Finds the single nop/jmp flip that makes the program terminate. Only the
+// instructions the unpatched program actually executes can be the culprit, and
+// flipping one of those fixes the program iff its new successor can reach
+// termination, so this runs in linear time for the original instruction set.
+// A program that already terminates doesn't need repairing.
+fn repair(code: &[Instruction], max_steps: Option<usize>) -> Option<Repair> {
+    let mut executed = vec![];
+    if let (Halt::Terminated, _) = trace_program(code, max_steps, |pc| executed.push(pc)) {
+        return None;
+    }
+
+    let reachable = reaches_termination(code);
+
+    let mut patched_code = code.to_vec();
+    let mut tried = vec![false; code.len()];
+
+    for index in executed {
+        if std::mem::replace(&mut tried[index], true) {
             continue;
         }
 
-        code[i] = code[i].flop();
-        let (terminated, acc) = run_program(&code);
+        let patched = match code[index].flop() {
+            Some(patched) => patched,
+            None => continue,
+        };
+
+        patched_code[index] = patched;
+        let fixed = successors(&patched_code, index)
+            .into_iter()
+            .any(|successor| reachable[successor]);
 
-        if terminated {
-            println!("part2: ({}, {})", i, acc);
+        // Conditional jumps make the static graph an over-approximation, so
+        // confirm the candidate before reporting it
+        if fixed {
+            if let (Halt::Terminated, machine) = run_program(&patched_code, max_steps) {
+                return Some(Repair {
+                    index,
+                    original: code[index],
+                    patched,
+                    acc: machine.acc(),
+                });
+            }
         }
 
-        code[i] = code[i].flop();
+        patched_code[index] = code[index];
+    }
+
+    None
+}
+
+mod test {
+    #[allow(unused)]
+    use super::*;
+
+    #[test]
+    fn parse_program_test() {
+        use Instruction::*;
+
+        let code = parse_program("nop +0\nacc -3\njmp +2\nadd b 4\njnz b -1\nout acc\nhlt\n");
+        assert_eq!(
+            Ok(vec![
+                Nop(0),
+                Acc(-3),
+                Jmp(2),
+                Add(Register::B, 4),
+                Jnz(Register::B, -1),
+                Out(Register::Acc),
+                Hlt
+            ]),
+            code
+        );
+
+        let err = parse_program("nop +0\nmul a 2\n").unwrap_err();
+        assert_eq!(2, err.line);
+        assert_eq!(ParseErrorKind::UnknownOpcode("mul".to_string()), err.kind);
+    }
+
+    #[test]
+    fn run_program_test() {
+        let code =
+            parse_program("add a 3\nacc +2\nadd a -1\njnz a -2\nout acc\nhlt\nacc +100\n").unwrap();
+        let (halt, machine) = run_program(&code, None);
+
+        assert_eq!(Halt::Terminated, halt);
+        assert_eq!(6, machine.acc());
+        assert_eq!(vec![6], machine.output);
+    }
+
+    #[test]
+    fn repair_test() {
+        use Instruction::*;
+
+        let code = parse_program(
+            "nop +0\nacc +1\njmp +4\nacc +3\njmp -3\nacc -99\nacc +1\njmp -4\nacc +6\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            Some(Repair {
+                index: 7,
+                original: Jmp(-4),
+                patched: Nop(-4),
+                acc: 8,
+            }),
+            repair(&code, None)
+        );
+
+        let code = parse_program("nop +1\nacc +1\n").unwrap();
+        assert_eq!(None, repair(&code, None));
+    }
+
+    #[test]
+    fn labels_and_round_trip_test() {
+        use Instruction::*;
+
+        let source = "start: add a 2 ; two iterations\nloop:\n  acc +1\n  add a -1\n  jnz a loop\n  jmp done\n  nop +0\ndone: hlt\n";
+        let code = parse_program(source).unwrap();
+        assert_eq!(
+            vec![
+                Add(Register::A, 2),
+                Acc(1),
+                Add(Register::A, -1),
+                Jnz(Register::A, -2),
+                Jmp(2),
+                Nop(0),
+                Hlt
+            ],
+            code
+        );
+
+        assert_eq!(Ok(code.clone()), parse_program(&disassemble(&code)));
+
+        let err = parse_program("jmp nowhere\n").unwrap_err();
+        assert_eq!(
+            ParseErrorKind::UnknownLabel("nowhere".to_string()),
+            err.kind
+        );
+    }
+
+    #[test]
+    fn basic_blocks_test() {
+        let code = parse_program(
+            "nop +0\nacc +1\njmp +4\nacc +3\njmp -3\nacc -99\nacc +1\njmp -4\nacc +6\n",
+        )
+        .unwrap();
+        assert_eq!(
+            vec![0..1, 1..3, 3..5, 5..6, 6..8, 8..9],
+            basic_blocks(&code)
+        );
+    }
+
+    #[test]
+    fn to_dot_test() {
+        let code = parse_program("nop +0\njmp -5\nacc +1\n").unwrap();
+        let dot = to_dot(&code);
+
+        assert!(dot.contains("    \"oob-4\" [label=\"-4\", shape=octagon];\n"));
+        assert!(dot.contains("    b0 -> \"oob-4\" [label=\"jmp\"];\n"));
+        assert!(dot.contains("    b1 -> end [label=\"\"];\n"));
+        assert!(!dot.contains(" oob"));
+    }
+
+    #[test]
+    fn halt_test() {
+        let code = parse_program("nop +0\njmp -2\n").unwrap();
+        assert_eq!(Halt::OutOfBounds(-1), run_program(&code, None).0);
+
+        let code = parse_program("acc +1\njmp +5\n").unwrap();
+        assert_eq!(Halt::OutOfBounds(6), run_program(&code, None).0);
+
+        let code = parse_program("acc +1\njmp -1\n").unwrap();
+        assert_eq!(Halt::InfiniteLoop, run_program(&code, None).0);
+
+        let code = parse_program("add a 1\njnz a -1\n").unwrap();
+        let (halt, machine) = run_program(&code, Some(10));
+        assert_eq!(Halt::BudgetExhausted, halt);
+        assert_eq!(5, machine.registers[Register::A.index()]);
     }
 }
@@ -1,50 +1,262 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, BufRead};
+
 static INPUT: &str = include_str!("../input.txt");
+static DEFAULT_PREAMBLE: usize = 25;
+
+// The last `size` numbers along with how many times each one occurs, so that
+// checking a number only needs one lookup per addend instead of every pair
+struct Window {
+    size: usize,
+    values: VecDeque<i64>,
+    counts: HashMap<i64, usize>,
+}
+
+impl Window {
+    fn new(size: usize) -> Self {
+        Self {
+            size,
+            values: VecDeque::with_capacity(size + 1),
+            counts: HashMap::with_capacity(size + 1),
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.values.len() == self.size
+    }
+
+    // Valid numbers are the sum of two different numbers in the window
+    fn is_valid(&self, number: i64) -> bool {
+        self.values.iter().any(|&addend| {
+            let other = number - addend;
+            other != addend && self.counts.contains_key(&other)
+        })
+    }
+
+    fn push(&mut self, number: i64) {
+        self.values.push_back(number);
+        *self.counts.entry(number).or_insert(0) += 1;
+
+        if self.values.len() > self.size {
+            let oldest = self.values.pop_front().unwrap();
+            let count = self.counts.get_mut(&oldest).unwrap();
+            *count -= 1;
+
+            if *count == 0 {
+                self.counts.remove(&oldest);
+            }
+        }
+    }
+
+    // Numbers seen before the window fills up are part of the preamble and
+    // always valid
+    fn check_and_push(&mut self, number: i64) -> bool {
+        let valid = !self.is_full() || self.is_valid(number);
+        self.push(number);
+        valid
+    }
+}
 
+// usage: day-09 [preamble] [--stdin]
 fn main() {
+    let mut preamble = DEFAULT_PREAMBLE;
+    let mut stream = false;
+
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--stdin" => stream = true,
+            _ => preamble = arg.parse().unwrap(),
+        }
+    }
+
+    if stream {
+        let stdin = io::stdin();
+        let result = validate_stream(stdin.lock(), preamble, |index, number| {
+            println!("invalid: {} at index {}", number, index);
+        });
+
+        if let Err(err) = result {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+
+        return;
+    }
+
     let numbers: Vec<i64> = INPUT.lines().map(|line| line.parse().unwrap()).collect();
 
-    let invalid_number = part1(&numbers);
+    let invalid_number = match part1(&numbers, preamble) {
+        Some(invalid_number) => invalid_number,
+        None => {
+            println!("part1 = no invalid numbers");
+            return;
+        }
+    };
     println!("part1 = {}", invalid_number);
 
-    let sum = part2(&numbers, invalid_number);
-    println!("part2 = {}", sum);
+    match part2(&numbers, invalid_number) {
+        Some(weakness) => println!("part2 = {}", weakness),
+        None => println!("part2 = no contiguous range sums to {}", invalid_number),
+    }
 }
 
-fn part1(numbers: &[i64]) -> i64 {
-    let mut start = 0;
-    let mut end = 25;
+// Reads one number per line, only ever holding on to the last `preamble`
+// numbers, and calls on_invalid as soon as an invalid number shows up
+fn validate_stream(
+    reader: impl BufRead,
+    preamble: usize,
+    mut on_invalid: impl FnMut(usize, i64),
+) -> io::Result<()> {
+    let mut window = Window::new(preamble);
+    let mut index = 0;
 
-    while is_valid(&numbers, start, end) {
-        start += 1;
-        end += 1;
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let number: i64 = line.parse().map_err(|_| {
+            let message = format!("line {}: not a number: {}", i + 1, line);
+            io::Error::new(io::ErrorKind::InvalidData, message)
+        })?;
+
+        if !window.check_and_push(number) {
+            on_invalid(index, number);
+        }
+
+        index += 1;
     }
 
-    numbers[end]
+    Ok(())
 }
 
-fn part2(numbers: &[i64], invalid_number: i64) -> i64 {
-    let mut window_size = 2;
+fn part1(numbers: &[i64], preamble: usize) -> Option<i64> {
+    let invalid = invalid_numbers(numbers, preamble);
 
-    loop {
-        for window in numbers.windows(window_size) {
-            let sum: i64 = window.iter().sum();
-            if sum == invalid_number {
-                return window[0] + window[window.len() - 1];
-            }
+    for (index, number) in invalid.iter().skip(1) {
+        println!("also invalid: {} at index {}", number, index);
+    }
+
+    invalid.first().map(|&(_, number)| number)
+}
+
+fn part2(numbers: &[i64], invalid_number: i64) -> Option<i64> {
+    let range = find_contiguous_range(numbers, invalid_number)?;
+    println!(
+        "range = {}..={} (min = {}, max = {})",
+        range.start, range.end, range.min, range.max
+    );
+
+    Some(range.weakness())
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct ContiguousRange {
+    start: usize,
+    end: usize, // inclusive
+    min: i64,
+    max: i64,
+}
+
+impl ContiguousRange {
+    fn weakness(&self) -> i64 {
+        self.min + self.max
+    }
+}
+
+// Finds a run of at least two numbers summing to target. prefix_sums maps the
+// sum of numbers[..i] to the first such i, so a range ending at `end` exists
+// iff (sum of numbers[..=end]) - target has been seen at least two numbers
+// back. Unlike a two-pointer search this still works with negative numbers.
+fn find_contiguous_range(numbers: &[i64], target: i64) -> Option<ContiguousRange> {
+    let mut prefix_sums: HashMap<i64, usize> = HashMap::with_capacity(numbers.len());
+    let mut previous_sum = 0;
+    let mut sum = 0;
+
+    for (end, &number) in numbers.iter().enumerate() {
+        sum += number;
+
+        if let Some(&start) = prefix_sums.get(&(sum - target)) {
+            let range = &numbers[start..=end];
+
+            return Some(ContiguousRange {
+                start,
+                end,
+                min: *range.iter().min().unwrap(),
+                max: *range.iter().max().unwrap(),
+            });
         }
 
-        window_size += 1;
+        prefix_sums.entry(previous_sum).or_insert(end);
+        previous_sum = sum;
     }
+
+    None
 }
 
-fn is_valid(numbers: &[i64], start: usize, end: usize) -> bool {
-    for i in start..end {
-        for j in 0..i {
-            if numbers[i] + numbers[j] == numbers[end] {
-                return true;
-            }
+// return type: [(index, number)] for every number after the preamble that
+// isn't the sum of two of the `preamble` numbers before it
+fn invalid_numbers(numbers: &[i64], preamble: usize) -> Vec<(usize, i64)> {
+    let mut window = Window::new(preamble);
+    let mut invalid = vec![];
+
+    for (index, &number) in numbers.iter().enumerate() {
+        if !window.check_and_push(number) {
+            invalid.push((index, number));
         }
     }
 
-    false
+    invalid
+}
+
+mod test {
+    #[allow(unused)]
+    use super::*;
+
+    #[test]
+    fn invalid_numbers_test() {
+        let numbers = [
+            35, 20, 15, 25, 47, 40, 62, 55, 65, 95, 102, 117, 150, 182, 127, 219, 299, 277, 309,
+            576,
+        ];
+
+        assert_eq!(vec![(14, 127)], invalid_numbers(&numbers, 5));
+        assert_eq!(vec![(3, 15)], invalid_numbers(&[1, 2, 3, 15, 18], 2));
+        assert_eq!(vec![(2, 4)], invalid_numbers(&[2, 2, 4], 2));
+    }
+
+    #[test]
+    fn find_contiguous_range_test() {
+        let numbers = [
+            35, 20, 15, 25, 47, 40, 62, 55, 65, 95, 102, 117, 150, 182, 127, 219, 299, 277, 309,
+            576,
+        ];
+
+        let range = find_contiguous_range(&numbers, 127).unwrap();
+        assert_eq!((2, 5), (range.start, range.end));
+        assert_eq!(62, range.weakness());
+
+        let range = find_contiguous_range(&[4, -7, 3, 10, -2], 6).unwrap();
+        assert_eq!((1, 3), (range.start, range.end));
+        assert_eq!((-7, 10), (range.min, range.max));
+
+        assert_eq!(None, find_contiguous_range(&[1, 2, 4], 4));
+        assert_eq!(None, find_contiguous_range(&[], 4));
+    }
+
+    #[test]
+    fn validate_stream_test() {
+        let input = "35\n20\n15\n25\n47\n40\n62\n55\n65\n95\n102\n117\n150\n182\n127\n219\n";
+        let mut invalid = vec![];
+        validate_stream(input.as_bytes(), 5, |index, number| {
+            invalid.push((index, number))
+        })
+        .unwrap();
+        assert_eq!(vec![(14, 127)], invalid);
+
+        let err = validate_stream("1\n2\nthree\n".as_bytes(), 2, |_, _| ()).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+    }
 }
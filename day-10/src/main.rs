@@ -1,71 +1,475 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+use std::fmt;
 
 static INPUT: &str = include_str!("../input.txt");
 
+// Allowed joltage difference between one adapter and the next
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Tolerance {
+    min: i64,
+    max: i64,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self { min: 1, max: 3 }
+    }
+}
+
+impl Tolerance {
+    fn allows(&self, difference: i64) -> bool {
+        self.min <= difference && difference <= self.max
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum ChainError {
+    NoAdapters,
+    Broken { from: i64, to: i64 },
+    Overflow,
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChainError::NoAdapters => write!(f, "no adapters"),
+            ChainError::Overflow => write!(f, "too many arrangements to count"),
+            ChainError::Broken { from, to } => {
+                write!(f, "can't get from {} jolts to {} jolts", from, to)
+            }
+        }
+    }
+}
+
+// usage: day-10 [min_difference max_difference] [--list N] [--sample SEED]
 fn main() {
+    let mut differences: Vec<i64> = vec![];
+    let mut list = 0;
+    let mut sample_seed = None;
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        let parsed = match arg.as_str() {
+            "--list" => args.next().and_then(|n| n.parse().ok()).map(|n| list = n),
+            "--sample" => args
+                .next()
+                .and_then(|seed| seed.parse().ok())
+                .map(|seed| sample_seed = Some(seed)),
+            _ => arg
+                .parse()
+                .ok()
+                .map(|difference| differences.push(difference)),
+        };
+
+        if parsed.is_none() {
+            usage();
+        }
+    }
+
+    let tolerance = match differences[..] {
+        [] => Tolerance::default(),
+        [min, max] if 0 <= min && min <= max => Tolerance { min, max },
+        _ => usage(),
+    };
+
     let mut adapters: Vec<i64> = INPUT.lines().map(|line| line.parse().unwrap()).collect();
 
     adapters.sort_unstable();
 
-    let diff = part1(&adapters);
-    println!("part1 = {}", diff);
+    match part1(&adapters, tolerance) {
+        Ok(diff) => println!("part1 = {}", diff),
+        Err(err) => {
+            println!("part1 = adapters don't form a chain: {}", err);
+            return;
+        }
+    }
+
+    match part2(&adapters, tolerance) {
+        Ok(number_of_arrangements) => println!("part2 = {}", number_of_arrangements),
+        Err(err) => println!("part2 = {}", err),
+    }
+
+    println!("mandatory = {:?}", mandatory_adapters(&adapters, tolerance));
+
+    for arrangement in Arrangements::new(&adapters, tolerance).take(list) {
+        println!("{:?}", arrangement);
+    }
 
-    let number_of_arrangements = part2(&adapters);
-    println!("part2 = {}", number_of_arrangements);
+    if let Some(seed) = sample_seed {
+        match sample_arrangement(&adapters, tolerance, &mut Rng::new(seed)) {
+            Ok(Some(arrangement)) => println!("sample = {:?}", arrangement),
+            Ok(None) => println!("sample = no arrangements"),
+            Err(err) => println!("sample = {}", err),
+        }
+    }
 }
 
-fn part1(adapters: &[i64]) -> i64 {
-    let initial_state = match adapters[0] {
-        1 => (1, 0),
-        3 => (0, 1),
-        _ => (0, 0),
-    };
+fn usage() -> ! {
+    eprintln!("usage: day-10 [min_difference max_difference] [--list N] [--sample SEED]");
+    std::process::exit(1);
+}
 
-    let (one_jolt_diffs, three_jolt_diffs) = adapters.windows(2).fold(
-        initial_state,
-        |(one_jolt_diffs, three_jolt_diffs), slice| match slice[1] - slice[0] {
-            1 => (one_jolt_diffs + 1, three_jolt_diffs),
-            3 => (one_jolt_diffs, three_jolt_diffs + 1),
-            _ => (one_jolt_diffs, three_jolt_diffs),
-        },
-    );
+fn part1(adapters: &[i64], tolerance: Tolerance) -> Result<usize, ChainError> {
+    let histogram = differences(adapters, tolerance)?;
 
-    one_jolt_diffs * (three_jolt_diffs + 1)
+    for (difference, count) in histogram.iter() {
+        println!("{} jolt differences: {}", difference, count);
+    }
+
+    let count = |difference| histogram.get(&difference).copied().unwrap_or(0);
+    Ok(count(1) * count(3))
 }
 
-fn part2(adapters: &[i64]) -> i64 {
-    let mut dyn_prog: HashMap<usize, i64> = HashMap::with_capacity(adapters.len());
-    dyn_prog.insert(0, 1);
+// Histogram of the differences along the full chain from the outlet (0 jolts)
+// through every adapter to the device (max tolerance above the last adapter)
+fn differences(adapters: &[i64], tolerance: Tolerance) -> Result<BTreeMap<i64, usize>, ChainError> {
+    let last = *adapters.last().ok_or(ChainError::NoAdapters)?;
+    let chain = std::iter::once(0)
+        .chain(adapters.iter().copied())
+        .chain(std::iter::once(last + tolerance.max));
+
+    let mut histogram = BTreeMap::new();
+    let mut previous = None;
+
+    for joltage in chain {
+        if let Some(previous) = previous {
+            let difference = joltage - previous;
+            if !tolerance.allows(difference) {
+                return Err(ChainError::Broken {
+                    from: previous,
+                    to: joltage,
+                });
+            }
+
+            *histogram.entry(difference).or_insert(0) += 1;
+        }
+
+        previous = Some(joltage);
+    }
+
+    Ok(histogram)
+}
+
+fn part2(adapters: &[i64], tolerance: Tolerance) -> Result<u128, ChainError> {
+    arrangements(adapters, tolerance)
+}
+
+// The outlet (0 jolts), every adapter, then the device (max tolerance above
+// the last adapter). Any arrangement is a path through this from start to end.
+fn joltages(adapters: &[i64], tolerance: Tolerance) -> Result<Vec<i64>, ChainError> {
+    let last = *adapters.last().ok_or(ChainError::NoAdapters)?;
+
+    let mut joltages = Vec::with_capacity(adapters.len() + 2);
+    joltages.push(0);
+    joltages.extend_from_slice(adapters);
+    joltages.push(last + tolerance.max);
+
+    Ok(joltages)
+}
+
+// Sorted joltages mean the nodes reachable from `from` are all just after it
+fn successors(
+    joltages: &[i64],
+    tolerance: Tolerance,
+    from: usize,
+) -> impl Iterator<Item = usize> + '_ {
+    (from + 1..joltages.len())
+        .take_while(move |&to| joltages[to] - joltages[from] <= tolerance.max)
+        .filter(move |&to| tolerance.allows(joltages[to] - joltages[from]))
+}
+
+// completions[i] is the number of ways to get from joltages[i] to the device
+fn completions(joltages: &[i64], tolerance: Tolerance) -> Result<Vec<u128>, ChainError> {
+    let mut completions = vec![0u128; joltages.len()];
+    completions[joltages.len() - 1] = 1;
+
+    for from in (0..joltages.len() - 1).rev() {
+        let mut total: u128 = 0;
+        for to in successors(joltages, tolerance, from) {
+            total = total
+                .checked_add(completions[to])
+                .ok_or(ChainError::Overflow)?;
+        }
+
+        completions[from] = total;
+    }
+
+    Ok(completions)
+}
+
+fn arrangements(adapters: &[i64], tolerance: Tolerance) -> Result<u128, ChainError> {
+    let joltages = joltages(adapters, tolerance)?;
+    Ok(completions(&joltages, tolerance)?[0])
+}
+
+// Yields every arrangement (the adapters used, in order) depth first, so
+// arrangements come out in lexicographic order of adapter positions
+struct Arrangements {
+    joltages: Vec<i64>,
+    tolerance: Tolerance,
+    // Whether each node has any way on to the device, so that the search
+    // never wanders into a dead end
+    leads_to_device: Vec<bool>,
+    // (node, next successor to try from it) for each node on the current path
+    stack: Vec<(usize, usize)>,
+}
+
+impl Arrangements {
+    fn new(adapters: &[i64], tolerance: Tolerance) -> Self {
+        let (joltages, stack) = match joltages(adapters, tolerance) {
+            Ok(joltages) => (joltages, vec![(0, 1)]),
+            Err(_) => (vec![], vec![]),
+        };
+
+        // Like completions, but without counts there's nothing to overflow
+        let mut leads_to_device = vec![false; joltages.len()];
+        for from in (0..joltages.len()).rev() {
+            leads_to_device[from] = from == joltages.len() - 1
+                || successors(&joltages, tolerance, from).any(|to| leads_to_device[to]);
+        }
 
-    ways(adapters, &mut dyn_prog, adapters.len() - 1)
+        Self {
+            joltages,
+            tolerance,
+            leads_to_device,
+            stack,
+        }
+    }
 }
 
-fn ways(adapters: &[i64], dyn_prog: &mut HashMap<usize, i64>, cursor: usize) -> i64 {
-    if dyn_prog.contains_key(&cursor) {
-        let rt = *dyn_prog.get(&cursor).unwrap();
-        // println!("ways down from {} = {}", adapters[cursor], rt);
-        rt
-    } else {
-        let adapter = adapters[cursor];
-        let mut blargh = [None, None, None];
+impl Iterator for Arrangements {
+    type Item = Vec<i64>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(&mut (node, ref mut next)) = self.stack.last_mut() {
+            let leads_to_device = &self.leads_to_device;
+            let successor = successors(&self.joltages, self.tolerance, node)
+                .find(|&to| to >= *next && leads_to_device[to]);
+
+            match successor {
+                Some(to) => {
+                    *next = to + 1;
+
+                    if to == self.joltages.len() - 1 {
+                        let arrangement = self.stack[1..]
+                            .iter()
+                            .map(|&(node, _)| self.joltages[node])
+                            .collect();
+                        return Some(arrangement);
+                    }
 
-        for i in 1..=3 {
-            let next_cursor = match cursor.checked_sub(i) {
-                Some(x) => x,
+                    self.stack.push((to, to + 1));
+                }
                 None => {
-                    blargh[3 - i] = Some(1);
-                    break;
+                    self.stack.pop();
                 }
-            };
+            }
+        }
+
+        None
+    }
+}
+
+// Small xorshift generator so sampling is reproducible from a seed
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // xorshift gets stuck on 0
+        Self(seed ^ 0x9e37_79b9_7f4a_7c15)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    // Uniform in 0..bound, rejecting the top sliver that would bias the modulo
+    fn below(&mut self, bound: u128) -> u128 {
+        let limit = u128::MAX - u128::MAX % bound;
+
+        loop {
+            let value = (u128::from(self.next_u64()) << 64) | u128::from(self.next_u64());
+            if value < limit {
+                return value % bound;
+            }
+        }
+    }
+}
+
+// Walks from the outlet, picking each next adapter with probability
+// proportional to the number of arrangements that continue through it, which
+// makes every complete arrangement equally likely
+fn sample_arrangement(
+    adapters: &[i64],
+    tolerance: Tolerance,
+    rng: &mut Rng,
+) -> Result<Option<Vec<i64>>, ChainError> {
+    let joltages = joltages(adapters, tolerance)?;
+    let completions = completions(&joltages, tolerance)?;
+    let device = joltages.len() - 1;
+
+    if completions[0] == 0 {
+        return Ok(None);
+    }
+
+    let mut arrangement = vec![];
+    let mut node = 0;
+
+    while node != device {
+        let mut choice = rng.below(completions[node]);
+
+        for to in successors(&joltages, tolerance, node) {
+            if choice < completions[to] {
+                node = to;
+                break;
+            }
+
+            choice -= completions[to];
+        }
+
+        if node != device {
+            arrangement.push(joltages[node]);
+        }
+    }
+
+    Ok(Some(arrangement))
+}
+
+// An adapter is in every arrangement iff no usable connection jumps over it
+fn mandatory_adapters(adapters: &[i64], tolerance: Tolerance) -> Vec<i64> {
+    let joltages = match joltages(adapters, tolerance) {
+        Ok(joltages) => joltages,
+        Err(_) => return vec![],
+    };
+    let device = joltages.len() - 1;
 
-            if adapter - adapters[next_cursor] <= 3 {
-                blargh[3 - i] = Some(ways(adapters, dyn_prog, next_cursor));
+    let mut from_outlet = vec![false; joltages.len()];
+    from_outlet[0] = true;
+    for from in 0..device {
+        if from_outlet[from] {
+            for to in successors(&joltages, tolerance, from) {
+                from_outlet[to] = true;
             }
         }
+    }
+
+    let mut to_device = vec![false; joltages.len()];
+    to_device[device] = true;
+    for from in (0..device).rev() {
+        to_device[from] = successors(&joltages, tolerance, from).any(|to| to_device[to]);
+    }
+
+    if !to_device[0] {
+        return vec![];
+    }
+
+    // jumped_over[i] > 0 iff some usable connection goes from before i to after i
+    let mut jumped_over = vec![0i64; joltages.len() + 1];
+    for from in (0..device).filter(|&from| from_outlet[from]) {
+        for to in successors(&joltages, tolerance, from).filter(|&to| to_device[to]) {
+            jumped_over[from + 1] += 1;
+            jumped_over[to] -= 1;
+        }
+    }
+
+    let mut covered = 0;
+    let mut mandatory = vec![];
+    for node in 1..device {
+        covered += jumped_over[node];
+        if covered == 0 {
+            mandatory.push(joltages[node]);
+        }
+    }
+
+    mandatory
+}
+
+mod test {
+    #[allow(unused)]
+    use super::*;
+
+    #[test]
+    fn differences_test() {
+        let adapters = [1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19];
+        let histogram = differences(&adapters, Tolerance::default()).unwrap();
+        assert_eq!(
+            vec![(1, 7), (3, 5)],
+            histogram.into_iter().collect::<Vec<_>>()
+        );
+
+        let histogram = differences(&[2, 4], Tolerance::default()).unwrap();
+        assert_eq!(
+            vec![(2, 2), (3, 1)],
+            histogram.into_iter().collect::<Vec<_>>()
+        );
+
+        assert_eq!(
+            Err(ChainError::Broken { from: 4, to: 8 }),
+            differences(&[1, 4, 8], Tolerance::default())
+        );
+        assert_eq!(
+            Err(ChainError::Broken { from: 0, to: 1 }),
+            differences(&[1, 3], Tolerance { min: 2, max: 3 })
+        );
+        assert_eq!(
+            Err(ChainError::NoAdapters),
+            differences(&[], Tolerance::default())
+        );
+    }
+
+    #[test]
+    fn arrangements_test() {
+        let adapters = [1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19];
+        assert_eq!(Ok(8), arrangements(&adapters, Tolerance::default()));
+
+        let mut adapters: Vec<i64> = include_str!("../test-input2.txt")
+            .lines()
+            .map(|line| line.parse().unwrap())
+            .collect();
+        adapters.sort_unstable();
+        assert_eq!(Ok(19208), arrangements(&adapters, Tolerance::default()));
+
+        let adapters: Vec<i64> = (1..=200).collect();
+        assert_eq!(
+            Err(ChainError::Overflow),
+            arrangements(&adapters, Tolerance::default())
+        );
+    }
+
+    #[test]
+    fn enumerate_arrangements_test() {
+        let adapters = [1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19];
+        let all: Vec<Vec<i64>> = Arrangements::new(&adapters, Tolerance::default()).collect();
+
+        assert_eq!(8, all.len());
+        assert_eq!(vec![1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19], all[0]);
+        assert_eq!(vec![1, 4, 7, 10, 12, 15, 16, 19], all[7]);
+
+        // Every path dead-ends at 60, which would take forever to find out
+        // without pruning
+        let mut dead_end: Vec<i64> = (1..=60).collect();
+        dead_end.push(100);
+        assert_eq!(
+            None,
+            Arrangements::new(&dead_end, Tolerance::default()).next()
+        );
+
+        let mut rng = Rng::new(2020);
+        for _ in 0..20 {
+            let sample = sample_arrangement(&adapters, Tolerance::default(), &mut rng);
+            assert!(all.contains(&sample.unwrap().unwrap()));
+        }
+
+        assert_eq!(
+            vec![1, 4, 7, 10, 12, 15, 16, 19],
+            mandatory_adapters(&adapters, Tolerance::default())
+        );
 
-        // println!("adapter = {}; blargh = {:?}", adapter, blargh);
-        let rt = blargh.iter().filter_map(|x| *x).sum();
-        dyn_prog.insert(cursor, rt);
-        rt
+        assert_eq!(0, Arrangements::new(&[1, 5], Tolerance::default()).count());
+        assert_eq!(0, Arrangements::new(&[], Tolerance::default()).count());
+        assert!(mandatory_adapters(&[1, 5], Tolerance::default()).is_empty());
     }
 }
@@ -1,30 +1,33 @@
-use std::convert::From;
+use std::convert::{From, TryFrom};
 use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
 
 static INPUT: &str = include_str!("../input.txt");
-static NUMBER_OF_COLUMNS: usize = 94;
-static NUMBER_OF_ROWS: usize = 97;
-
 // static INPUT: &str = include_str!("../test-input.txt");
-// static NUMBER_OF_COLUMNS: usize = 10;
-// static NUMBER_OF_ROWS: usize = 10;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 enum Tile {
     Floor,
     Empty,
     Occupied,
 }
 
-impl From<char> for Tile {
-    fn from(character: char) -> Self {
+impl TryFrom<char> for Tile {
+    type Error = char;
+
+    fn try_from(character: char) -> Result<Self, Self::Error> {
         use Tile::*;
 
         match character {
-            '.' => Floor,
-            'L' => Empty,
-            '#' => Occupied,
-            _ => unreachable!(),
+            '.' => Ok(Floor),
+            'L' => Ok(Empty),
+            '#' => Ok(Occupied),
+            _ => Err(character),
         }
     }
 }
@@ -53,64 +56,215 @@ impl From<&Tile> for u8 {
 }
 
 impl Tile {
-    fn next_1(&self, occupied_neighbors: u8) -> Self {
+    fn next(&self, occupied_neighbors: u8, rules: &Rules) -> Self {
         use Tile::*;
 
-        match (self, occupied_neighbors) {
-            (Floor, _) => Floor,
-            (Empty, 0) => Occupied,
-            (Empty, _) => Empty,
-            (Occupied, 4..=8) => Empty,
-            (Occupied, _) => Occupied,
+        match self {
+            Floor => Floor,
+            Empty if occupied_neighbors <= rules.sit_at_most => Occupied,
+            Occupied if occupied_neighbors >= rules.leave_at_least => Empty,
+            Empty => Empty,
+            Occupied => Occupied,
         }
     }
+}
 
-    fn next_2(&self, visible_neighbors: u8) -> Self {
-        use Tile::*;
+static ALL_DIRECTIONS: [(isize, isize); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+
+static ORTHOGONAL_DIRECTIONS: [(isize, isize); 4] = [(-1, 0), (0, -1), (0, 1), (1, 0)];
+
+// Which seats count as a seat's neighbors
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Neighborhood {
+    // The eight surrounding tiles
+    Adjacent,
+    // The first seat in each of the eight directions
+    LineOfSight,
+    // The first seat in each of the eight directions, up to this many tiles away
+    Sight(usize),
+    // The four orthogonally adjacent tiles
+    VonNeumann,
+}
+
+impl FromStr for Neighborhood {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        use Neighborhood::*;
 
-        match (self, visible_neighbors) {
-            (Floor, _) => Floor,
-            (Empty, 0) => Occupied,
-            (Empty, _) => Empty,
-            (Occupied, 5..=8) => Empty,
-            (Occupied, _) => Occupied,
+        match string {
+            "adjacent" => Ok(Adjacent),
+            "sight" => Ok(LineOfSight),
+            "von-neumann" => Ok(VonNeumann),
+            _ => match string.strip_prefix("sight:").map(str::parse) {
+                Some(Ok(radius)) => Ok(Sight(radius)),
+                _ => Err(format!("unknown neighborhood: {}", string)),
+            },
         }
     }
 }
 
-#[derive(Default, PartialEq, Eq)]
-struct Map {
-    map: Vec<Vec<Tile>>,
+impl Neighborhood {
+    // return type: (directions, how far to look in each one)
+    fn rays(self) -> (&'static [(isize, isize)], Option<usize>) {
+        use Neighborhood::*;
+
+        match self {
+            Adjacent => (&ALL_DIRECTIONS, Some(1)),
+            LineOfSight => (&ALL_DIRECTIONS, None),
+            Sight(radius) => (&ALL_DIRECTIONS, Some(radius)),
+            VonNeumann => (&ORTHOGONAL_DIRECTIONS, Some(1)),
+        }
+    }
 }
 
-impl Map {
-    fn next_1(&self) -> Self {
-        let mut new = Map::default();
-        for y in 0..NUMBER_OF_ROWS {
-            new.map.push(vec![]);
-
-            for x in 0..NUMBER_OF_COLUMNS {
-                let occupied_neighbors = occupied_neighbors(&self.map, y, x);
-                new.map[y].push(self.map[y][x].next_1(occupied_neighbors));
-            }
+// Empty seats fill up when at most `sit_at_most` neighbors are occupied and
+// occupied seats empty out when at least `leave_at_least` neighbors are
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Rules {
+    neighborhood: Neighborhood,
+    sit_at_most: u8,
+    leave_at_least: u8,
+}
+
+static PART1_RULES: Rules = Rules {
+    neighborhood: Neighborhood::Adjacent,
+    sit_at_most: 0,
+    leave_at_least: 4,
+};
+
+static PART2_RULES: Rules = Rules {
+    neighborhood: Neighborhood::LineOfSight,
+    sit_at_most: 0,
+    leave_at_least: 5,
+};
+
+// Once sit_at_most < leave_at_least the rules make a symmetric threshold
+// network, which always either settles or ends up flipping back and forth
+// between two layouts, so a run that never settles is caught by watching for
+// the same seats changing twice in a row
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Oscillation {
+    generation: usize,
+}
+
+impl fmt::Display for Oscillation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "never settles: generation {} is the same as generation {}",
+            self.generation,
+            self.generation - 2
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum MapError {
+    Empty,
+    InvalidTile {
+        row: usize,
+        column: usize,
+        character: char,
+    },
+    Ragged {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use MapError::*;
+
+        match self {
+            Empty => write!(f, "the layout is empty"),
+            InvalidTile {
+                row,
+                column,
+                character,
+            } => write!(
+                f,
+                "row {}, column {}: invalid tile {:?}",
+                row + 1,
+                column + 1,
+                character
+            ),
+            Ragged {
+                row,
+                expected,
+                found,
+            } => write!(
+                f,
+                "row {} has {} columns but the first row has {}",
+                row + 1,
+                found,
+                expected
+            ),
         }
-        new
     }
+}
 
-    fn next_2(&self) -> Self {
-        let mut new = Map::default();
-        for y in 0..NUMBER_OF_ROWS {
-            new.map.push(vec![]);
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Map {
+    map: Vec<Vec<Tile>>,
+    rows: usize,
+    columns: usize,
+}
 
-            for x in 0..NUMBER_OF_COLUMNS {
-                let visible_neighbors = visible_neighbors(&self.map, y, x);
-                // println!("({}, {}) => {}", y, x, visible_neighbors);
-                new.map[y].push(self.map[y][x].next_2(visible_neighbors));
+impl FromStr for Map {
+    type Err = MapError;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        let mut map: Vec<Vec<Tile>> = vec![];
+
+        for (row, line) in string.lines().enumerate() {
+            let tiles = line
+                .chars()
+                .enumerate()
+                .map(|(column, character)| {
+                    Tile::try_from(character).map_err(|character| MapError::InvalidTile {
+                        row,
+                        column,
+                        character,
+                    })
+                })
+                .collect::<Result<Vec<Tile>, MapError>>()?;
+
+            if let Some(first) = map.first() {
+                if first.len() != tiles.len() {
+                    return Err(MapError::Ragged {
+                        row,
+                        expected: first.len(),
+                        found: tiles.len(),
+                    });
+                }
             }
+
+            map.push(tiles);
         }
-        new
+
+        let rows = map.len();
+        let columns = map.first().map_or(0, Vec::len);
+        if columns == 0 {
+            return Err(MapError::Empty);
+        }
+
+        Ok(Map { map, rows, columns })
     }
+}
 
+impl Map {
     fn occupied_seats(&self) -> usize {
         self.map
             .iter()
@@ -122,7 +276,7 @@ impl Map {
 
 impl fmt::Display for Map {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut s = String::with_capacity(NUMBER_OF_COLUMNS * NUMBER_OF_ROWS);
+        let mut s = String::with_capacity((self.columns + 1) * self.rows);
         for row in self.map.iter() {
             for tile in row {
                 s.push(tile.into());
@@ -136,225 +290,719 @@ impl fmt::Display for Map {
     }
 }
 
+// usage: day-11 [--neighborhood adjacent|sight|sight:N|von-neumann] [--sit N] [--leave N]
+//               [--animate] [--delay MS] [--frames DIR] [--threads N] [--bench]
 fn main() {
-    part1();
-    part2();
+    let map: Map = match INPUT.parse() {
+        Ok(map) => map,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() {
+        part1(&map);
+        part2(&map);
+        return;
+    }
+
+    let options = match parse_options(&args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    if options.bench {
+        bench(&map, options.threads);
+        return;
+    }
+
+    if !options.animate && options.frames.is_none() {
+        let result = match options.threads {
+            None | Some(1) => simulate(&map, &options.rules),
+            Some(threads) => simulate_parallel(&map, &options.rules, threads),
+        };
+
+        match result {
+            Ok(map) => println!("{:?} = {}", options.rules, map.occupied_seats()),
+            Err(oscillation) => println!("{:?}: {}", options.rules, oscillation),
+        }
+        return;
+    }
+
+    if let Err(err) = play(&map, &options) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
 }
 
-fn part1() {
-    let map: Vec<Vec<Tile>> = INPUT
-        .lines()
-        .map(str::chars)
-        .map(|chars| chars.map(Tile::from))
-        .map(Iterator::collect)
-        .collect();
+struct Options {
+    rules: Rules,
+    animate: bool,
+    delay: Duration,
+    frames: Option<PathBuf>,
+    threads: Option<usize>,
+    bench: bool,
+}
 
-    let mut map = Map { map };
-    let mut next_map = map.next_1();
+// Any rules not given on the command line come from the part 1 rules
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        rules: PART1_RULES,
+        animate: false,
+        delay: Duration::from_millis(200),
+        frames: None,
+        threads: None,
+        bench: false,
+    };
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--animate" => {
+                options.animate = true;
+                continue;
+            }
+            "--bench" => {
+                options.bench = true;
+                continue;
+            }
+            _ => (),
+        }
+
+        let value = args
+            .next()
+            .ok_or_else(|| format!("{} needs a value", arg))?;
+
+        match arg.as_str() {
+            "--neighborhood" => options.rules.neighborhood = value.parse()?,
+            "--sit" => options.rules.sit_at_most = parse_number(arg, value)?,
+            "--leave" => options.rules.leave_at_least = parse_number(arg, value)?,
+            "--delay" => options.delay = Duration::from_millis(parse_number(arg, value)?),
+            "--frames" => options.frames = Some(PathBuf::from(value)),
+            "--threads" => match parse_number(arg, value)? {
+                0 => return Err("--threads needs at least one thread".to_string()),
+                threads => options.threads = Some(threads),
+            },
+            _ => return Err(format!("unknown option: {}", arg)),
+        }
+    }
 
-    while map != next_map {
-        map.map = next_map.map;
-        next_map = map.next_1();
+    if options.rules.sit_at_most >= options.rules.leave_at_least {
+        return Err("--sit has to be less than --leave".to_string());
     }
 
-    println!("part1 = {}", map.occupied_seats());
+    Ok(options)
+}
+
+fn parse_number<T: FromStr>(arg: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("{} needs a number", arg))
 }
 
-fn part2() {
-    let map: Vec<Vec<Tile>> = INPUT
-        .lines()
-        .map(str::chars)
-        .map(|chars| chars.map(Tile::from))
-        .map(Iterator::collect)
-        .collect();
+// Steps through the simulation one generation at a time, drawing each one in
+// the terminal and/or writing it to DIR/generation-NNNN.txt
+fn play(map: &Map, options: &Options) -> io::Result<()> {
+    if let Some(frames) = &options.frames {
+        fs::create_dir_all(frames)?;
+    }
+
+    let mut simulation = Simulation::new(map, &options.rules);
+    let mut changed = vec![];
+    let mut generation = 0;
+
+    loop {
+        if options.animate {
+            let mut stdout = io::stdout();
+            write!(
+                stdout,
+                "\x1b[2J\x1b[H{}",
+                highlight(&simulation.map, &changed)
+            )?;
+            writeln!(
+                stdout,
+                "generation {}: {} changed, {} occupied",
+                generation,
+                changed.len(),
+                simulation.map.occupied_seats()
+            )?;
+            stdout.flush()?;
+
+            thread::sleep(options.delay);
+        }
 
-    let mut map = Map { map };
-    let mut next_map = map.next_2();
+        if let Some(frames) = &options.frames {
+            let path = frames.join(format!("generation-{:04}.txt", generation));
+            fs::write(path, simulation.map.to_string())?;
+        }
 
-    while map != next_map {
-        map.map = next_map.map;
-        next_map = map.next_2();
+        changed = simulation.step();
+        if changed.is_empty() {
+            return Ok(());
+        }
+
+        if simulation.is_oscillating() {
+            println!("{}", simulation.oscillation());
+            return Ok(());
+        }
+
+        generation += 1;
     }
+}
+
+// Like Map's Display but with the given tiles in reverse video
+fn highlight(map: &Map, changed: &[(usize, usize)]) -> String {
+    let mut is_changed = vec![vec![false; map.columns]; map.rows];
+    for &(y, x) in changed {
+        is_changed[y][x] = true;
+    }
+
+    let mut s = String::with_capacity((map.columns + 1) * map.rows);
+    for (y, row) in map.map.iter().enumerate() {
+        for (x, tile) in row.iter().enumerate() {
+            if is_changed[y][x] {
+                s.push_str("\x1b[7m");
+                s.push(tile.into());
+                s.push_str("\x1b[0m");
+            } else {
+                s.push(tile.into());
+            }
+        }
 
-    println!("part2 = {}", map.occupied_seats());
+        s.push('\n');
+    }
+
+    s
 }
 
-fn occupied_neighbors(map: &[Vec<Tile>], y: usize, x: usize) -> u8 {
-    if y == 0 {
-        if x == 0 {
-            u8::from(&map[y][x + 1]) + u8::from(&map[y + 1][x]) + u8::from(&map[y + 1][x + 1])
-        } else if x == NUMBER_OF_COLUMNS - 1 {
-            u8::from(&map[y][x - 1]) + u8::from(&map[y + 1][x - 1]) + u8::from(&map[y + 1][x])
-        } else {
-            u8::from(&map[y][x - 1])
-                + u8::from(&map[y][x + 1])
-                + u8::from(&map[y + 1][x - 1])
-                + u8::from(&map[y + 1][x])
-                + u8::from(&map[y + 1][x + 1])
-        }
-    } else if y == NUMBER_OF_ROWS - 1 {
-        if x == 0 {
-            u8::from(&map[y][x + 1]) + u8::from(&map[y - 1][x]) + u8::from(&map[y - 1][x + 1])
-        } else if x == NUMBER_OF_COLUMNS - 1 {
-            u8::from(&map[y][x - 1]) + u8::from(&map[y - 1][x - 1]) + u8::from(&map[y - 1][x])
-        } else {
-            u8::from(&map[y][x - 1])
-                + u8::from(&map[y][x + 1])
-                + u8::from(&map[y - 1][x - 1])
-                + u8::from(&map[y - 1][x])
-                + u8::from(&map[y - 1][x + 1])
+// Every seat (floor never changes, so it's left out) along with the seats
+// that count as its neighbors. Working these out once up front means the
+// simulation never has to walk across the floor again.
+struct SeatGraph {
+    seats: Vec<(usize, usize)>,
+    neighbors: Vec<Vec<usize>>,
+}
+
+impl SeatGraph {
+    fn new(map: &Map, neighborhood: Neighborhood) -> Self {
+        let mut index = vec![vec![None; map.columns]; map.rows];
+        let mut seats = vec![];
+
+        for (y, row) in map.map.iter().enumerate() {
+            for (x, tile) in row.iter().enumerate() {
+                if *tile != Tile::Floor {
+                    index[y][x] = Some(seats.len());
+                    seats.push((y, x));
+                }
+            }
         }
-    } else if x == 0 {
-        u8::from(&map[y - 1][x])
-            + u8::from(&map[y - 1][x + 1])
-            + u8::from(&map[y][x + 1])
-            + u8::from(&map[y + 1][x])
-            + u8::from(&map[y + 1][x + 1])
-    } else if x == NUMBER_OF_COLUMNS - 1 {
-        u8::from(&map[y - 1][x - 1])
-            + u8::from(&map[y - 1][x])
-            + u8::from(&map[y][x - 1])
-            + u8::from(&map[y + 1][x])
-            + u8::from(&map[y + 1][x - 1])
-    } else {
-        u8::from(&map[y - 1][x - 1])
-            + u8::from(&map[y - 1][x])
-            + u8::from(&map[y - 1][x + 1])
-            + u8::from(&map[y][x - 1])
-            + u8::from(&map[y][x + 1])
-            + u8::from(&map[y + 1][x - 1])
-            + u8::from(&map[y + 1][x])
-            + u8::from(&map[y + 1][x + 1])
+
+        let (directions, radius) = neighborhood.rays();
+        let neighbors = seats
+            .iter()
+            .map(|&(y, x)| {
+                directions
+                    .iter()
+                    .filter_map(|&(dy, dx)| first_seat(map, y, x, dy, dx, radius))
+                    .filter_map(|(y, x)| index[y][x])
+                    .collect()
+            })
+            .collect();
+
+        Self { seats, neighbors }
     }
 }
 
-fn visible_neighbors(map: &[Vec<Tile>], y: usize, x: usize) -> u8 {
-    up_left_neighbor(map, y, x)
-        + up_neighbor(map, y, x)
-        + up_right_neighbor(map, y, x)
-        + left_neighbor(map, y, x)
-        + right_neighbor(map, y, x)
-        + down_left_neighbor(map, y, x)
-        + down_neighbor(map, y, x)
-        + down_right_neighbor(map, y, x)
+// Keeps a running count of each seat's occupied neighbors so that a step only
+// has to look at the seats next to the ones that changed in the last step
+struct Simulation {
+    map: Map,
+    rules: Rules,
+    graph: SeatGraph,
+    occupied_neighbors: Vec<u8>,
+    to_check: Vec<usize>,
+    // Whether each seat is already in to_check, all false between steps
+    queued: Vec<bool>,
+    generation: usize,
+    // Sorted, to compare against the next step's changes
+    last_changed: Vec<usize>,
+    oscillating: bool,
 }
 
-fn up_left_neighbor(map: &[Vec<Tile>], mut y: usize, mut x: usize) -> u8 {
-    if y == 0 || x == 0 {
-        return 0;
+impl Simulation {
+    fn new(map: &Map, rules: &Rules) -> Self {
+        let graph = SeatGraph::new(map, rules.neighborhood);
+        let occupied_neighbors = graph
+            .neighbors
+            .iter()
+            .map(|neighbors| {
+                neighbors
+                    .iter()
+                    .map(|&seat| {
+                        let (y, x) = graph.seats[seat];
+                        u8::from(&map.map[y][x])
+                    })
+                    .sum()
+            })
+            .collect();
+        let to_check = (0..graph.seats.len()).collect();
+        let queued = vec![false; graph.seats.len()];
+
+        Self {
+            map: map.clone(),
+            rules: *rules,
+            graph,
+            occupied_neighbors,
+            to_check,
+            queued,
+            generation: 0,
+            last_changed: vec![],
+            oscillating: false,
+        }
     }
 
-    y -= 1;
-    x -= 1;
+    // Seats only ever flip between empty and occupied, so if the same seats
+    // change twice in a row the layout is back where it was two steps ago
+    fn is_oscillating(&self) -> bool {
+        self.oscillating
+    }
 
-    while y > 0 && x > 0 && map[y][x] == Tile::Floor {
-        y -= 1;
-        x -= 1;
+    fn oscillation(&self) -> Oscillation {
+        Oscillation {
+            generation: self.generation,
+        }
     }
 
-    u8::from(&map[y][x])
-}
+    fn queue(&mut self, seat: usize) {
+        if !self.queued[seat] {
+            self.queued[seat] = true;
+            self.to_check.push(seat);
+        }
+    }
+
+    fn tile(&self, seat: usize) -> Tile {
+        let (y, x) = self.graph.seats[seat];
+        self.map.map[y][x]
+    }
+
+    // Advances one generation and returns the positions of the seats that
+    // changed. Nothing changing means the layout has settled.
+    fn step(&mut self) -> Vec<(usize, usize)> {
+        // Work out every change before applying any so that the counts all
+        // still describe the previous generation
+        let changed: Vec<(usize, Tile)> = self
+            .to_check
+            .iter()
+            .filter_map(|&seat| {
+                let tile = self.tile(seat);
+                let next = tile.next(self.occupied_neighbors[seat], &self.rules);
+                if next != tile {
+                    Some((seat, next))
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        self.to_check.clear();
+
+        for &(seat, tile) in changed.iter() {
+            let (y, x) = self.graph.seats[seat];
+            self.map.map[y][x] = tile;
+
+            // Every neighborhood is symmetric, so the seats that can see this
+            // one are exactly the ones it can see
+            self.queue(seat);
+            for i in 0..self.graph.neighbors[seat].len() {
+                let neighbor = self.graph.neighbors[seat][i];
+                if tile == Tile::Occupied {
+                    self.occupied_neighbors[neighbor] += 1;
+                } else {
+                    self.occupied_neighbors[neighbor] -= 1;
+                }
+
+                self.queue(neighbor);
+            }
+        }
+
+        for &seat in self.to_check.iter() {
+            self.queued[seat] = false;
+        }
 
-fn up_neighbor(map: &[Vec<Tile>], mut y: usize, x: usize) -> u8 {
-    if y == 0 {
-        return 0;
+        let mut seats: Vec<usize> = changed.iter().map(|&(seat, _)| seat).collect();
+        seats.sort_unstable();
+        self.oscillating = !seats.is_empty() && seats == self.last_changed;
+        self.last_changed = seats;
+        self.generation += 1;
+
+        changed
+            .into_iter()
+            .map(|(seat, _)| self.graph.seats[seat])
+            .collect()
     }
+}
 
-    y -= 1;
+// Runs the rules until nothing changes
+fn simulate(map: &Map, rules: &Rules) -> Result<Map, Oscillation> {
+    let mut simulation = Simulation::new(map, rules);
 
-    while y > 0 && map[y][x] == Tile::Floor {
-        y -= 1;
+    while !simulation.step().is_empty() {
+        if simulation.is_oscillating() {
+            return Err(simulation.oscillation());
+        }
     }
 
-    u8::from(&map[y][x])
+    Ok(simulation.map)
+}
+
+// What a generation of a Grid did
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Step {
+    Changed,
+    Settled,
+    Oscillating,
 }
 
-fn up_right_neighbor(map: &[Vec<Tile>], mut y: usize, mut x: usize) -> u8 {
-    if y == 0 || x == NUMBER_OF_COLUMNS - 1 {
-        return 0;
+// A flat copy of the map with a second buffer for the next generation, so a
+// whole generation can be computed in place and split up by rows
+struct Grid {
+    columns: usize,
+    rules: Rules,
+    tiles: Vec<Tile>,
+    next: Vec<Tile>,
+    // The generation before tiles, to catch layouts that flip back and forth
+    before: Vec<Tile>,
+    // Indexes into tiles of each tile's neighbors (none for floor)
+    neighbors: Vec<Vec<usize>>,
+}
+
+impl Grid {
+    fn new(map: &Map, rules: &Rules) -> Self {
+        let graph = SeatGraph::new(map, rules.neighborhood);
+        let flat = |(y, x): (usize, usize)| y * map.columns + x;
+
+        let mut neighbors = vec![vec![]; map.rows * map.columns];
+        for (seat, &position) in graph.seats.iter().enumerate() {
+            neighbors[flat(position)] = graph.neighbors[seat]
+                .iter()
+                .map(|&neighbor| flat(graph.seats[neighbor]))
+                .collect();
+        }
+
+        let tiles: Vec<Tile> = map.map.iter().flatten().copied().collect();
+
+        Self {
+            columns: map.columns,
+            rules: *rules,
+            next: tiles.clone(),
+            before: tiles.clone(),
+            tiles,
+            neighbors,
+        }
+    }
+
+    fn step(&mut self) -> Step {
+        let changed = next_tiles(&self.tiles, &self.neighbors, &self.rules, 0, &mut self.next);
+        self.advance(changed)
     }
 
-    y -= 1;
-    x += 1;
+    // Same as step but with the rows shared out between threads
+    fn step_parallel(&mut self, threads: usize) -> Step {
+        let rows = self.tiles.len() / self.columns;
+        let chunk = rows.div_ceil(threads) * self.columns;
+        let (tiles, neighbors, rules) = (&self.tiles, &self.neighbors, &self.rules);
+        let next = &mut self.next;
+
+        let changed = thread::scope(|scope| {
+            let handles: Vec<_> = next
+                .chunks_mut(chunk)
+                .enumerate()
+                .map(|(i, next)| {
+                    scope.spawn(move || next_tiles(tiles, neighbors, rules, i * chunk, next))
+                })
+                .collect();
+
+            let mut changed = false;
+            for handle in handles {
+                changed |= handle.join().unwrap();
+            }
 
-    while y > 0 && x < NUMBER_OF_COLUMNS - 1 && map[y][x] == Tile::Floor {
-        y -= 1;
-        x += 1;
+            changed
+        });
+
+        self.advance(changed)
     }
 
-    u8::from(&map[y][x])
-}
+    // Makes `next` the current generation
+    fn advance(&mut self, changed: bool) -> Step {
+        let step = if !changed {
+            Step::Settled
+        } else if self.next == self.before {
+            Step::Oscillating
+        } else {
+            Step::Changed
+        };
+
+        std::mem::swap(&mut self.before, &mut self.tiles);
+        std::mem::swap(&mut self.tiles, &mut self.next);
+        step
+    }
 
-fn left_neighbor(map: &[Vec<Tile>], y: usize, mut x: usize) -> u8 {
-    if x == 0 {
-        return 0;
+    fn to_map(&self) -> Map {
+        let map: Vec<Vec<Tile>> = self
+            .tiles
+            .chunks(self.columns)
+            .map(<[Tile]>::to_vec)
+            .collect();
+
+        Map {
+            rows: map.len(),
+            columns: self.columns,
+            map,
+        }
     }
+}
 
-    x -= 1;
+// Fills `next` with the next generation of tiles[start..start + next.len()]
+// and returns whether any of them changed
+fn next_tiles(
+    tiles: &[Tile],
+    neighbors: &[Vec<usize>],
+    rules: &Rules,
+    start: usize,
+    next: &mut [Tile],
+) -> bool {
+    let mut changed = false;
+
+    for (i, next) in next.iter_mut().enumerate() {
+        let tile = tiles[start + i];
+        let occupied_neighbors = neighbors[start + i]
+            .iter()
+            .map(|&neighbor| u8::from(&tiles[neighbor]))
+            .sum();
 
-    while x > 0 && map[y][x] == Tile::Floor {
-        x -= 1;
+        *next = tile.next(occupied_neighbors, rules);
+        changed |= *next != tile;
     }
 
-    u8::from(&map[y][x])
+    changed
+}
+
+fn simulate_serial(map: &Map, rules: &Rules) -> Result<Map, Oscillation> {
+    let mut grid = Grid::new(map, rules);
+    run_grid(&mut grid, Grid::step)
 }
 
-fn right_neighbor(map: &[Vec<Tile>], y: usize, mut x: usize) -> u8 {
-    if x == NUMBER_OF_COLUMNS - 1 {
-        return 0;
+fn simulate_parallel(map: &Map, rules: &Rules, threads: usize) -> Result<Map, Oscillation> {
+    let mut grid = Grid::new(map, rules);
+    run_grid(&mut grid, |grid| grid.step_parallel(threads))
+}
+
+fn run_grid(grid: &mut Grid, step: impl Fn(&mut Grid) -> Step) -> Result<Map, Oscillation> {
+    let mut generation = 0;
+
+    loop {
+        generation += 1;
+
+        match step(grid) {
+            Step::Changed => (),
+            Step::Settled => return Ok(grid.to_map()),
+            Step::Oscillating => return Err(Oscillation { generation }),
+        }
     }
+}
+
+// Times each way of running the simulation to completion for both parts. The
+// parallel run uses every core unless told otherwise.
+fn bench(map: &Map, threads: Option<usize>) {
+    let threads = threads
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |threads| threads.get()));
+    let iterations = 20;
 
-    x += 1;
+    for (name, rules) in [("part1", &PART1_RULES), ("part2", &PART2_RULES)].iter() {
+        let expected = simulate(map, rules);
+
+        let time = |label: &str, run: &dyn Fn() -> Result<Map, Oscillation>| {
+            let start = Instant::now();
+            for _ in 0..iterations {
+                assert_eq!(expected, run());
+            }
 
-    while x < NUMBER_OF_COLUMNS - 1 && map[y][x] == Tile::Floor {
-        x += 1;
+            println!(
+                "{} {:<20} {:?} per run",
+                name,
+                label,
+                start.elapsed() / iterations
+            );
+        };
+
+        time("incremental", &|| simulate(map, rules));
+        time("serial", &|| simulate_serial(map, rules));
+        time(&format!("parallel ({} threads)", threads), &|| {
+            simulate_parallel(map, rules, threads)
+        });
     }
+}
 
-    u8::from(&map[y][x])
+fn part1(map: &Map) {
+    match simulate(map, &PART1_RULES) {
+        Ok(map) => println!("part1 = {}", map.occupied_seats()),
+        Err(oscillation) => println!("part1 = {}", oscillation),
+    }
 }
 
-fn down_left_neighbor(map: &[Vec<Tile>], mut y: usize, mut x: usize) -> u8 {
-    if y == NUMBER_OF_ROWS - 1 || x == 0 {
-        return 0;
+fn part2(map: &Map) {
+    match simulate(map, &PART2_RULES) {
+        Ok(map) => println!("part2 = {}", map.occupied_seats()),
+        Err(oscillation) => println!("part2 = {}", oscillation),
     }
+}
 
-    y += 1;
-    x -= 1;
+// Walks from (y, x) in direction (dy, dx) for at most `radius` tiles (or until
+// the edge of the map) and returns the position of the first seat found
+fn first_seat(
+    map: &Map,
+    y: usize,
+    x: usize,
+    dy: isize,
+    dx: isize,
+    radius: Option<usize>,
+) -> Option<(usize, usize)> {
+    let mut y = y as isize;
+    let mut x = x as isize;
+    let mut distance = 0;
+
+    while radius != Some(distance) {
+        y += dy;
+        x += dx;
+        distance += 1;
+
+        if y < 0 || x < 0 || y as usize >= map.rows || x as usize >= map.columns {
+            break;
+        }
 
-    while y < NUMBER_OF_ROWS - 1 && x > 0 && map[y][x] == Tile::Floor {
-        y += 1;
-        x -= 1;
+        if map.map[y as usize][x as usize] != Tile::Floor {
+            return Some((y as usize, x as usize));
+        }
     }
 
-    u8::from(&map[y][x])
+    None
 }
 
-fn down_neighbor(map: &[Vec<Tile>], mut y: usize, x: usize) -> u8 {
-    if y == NUMBER_OF_ROWS - 1 {
-        return 0;
+mod test {
+    #[allow(unused)]
+    use super::*;
+
+    // What the simulation starts out counting for the seat at (y, x)
+    #[allow(unused)]
+    fn occupied_neighbors(map: &Map, y: usize, x: usize, neighborhood: Neighborhood) -> u8 {
+        let rules = Rules {
+            neighborhood,
+            ..PART1_RULES
+        };
+        let simulation = Simulation::new(map, &rules);
+        let seat = simulation
+            .graph
+            .seats
+            .iter()
+            .position(|&seat| seat == (y, x));
+
+        simulation.occupied_neighbors[seat.unwrap()]
+    }
+
+    #[test]
+    fn parse_map_test() {
+        let map: Map = include_str!("../test-input.txt").parse().unwrap();
+        assert_eq!((10, 10), (map.rows, map.columns));
+
+        assert_eq!(
+            Err(MapError::Ragged {
+                row: 1,
+                expected: 3,
+                found: 2
+            }),
+            "L.L\nLL\n".parse::<Map>()
+        );
+        assert_eq!(Err(MapError::Empty), "".parse::<Map>());
     }
 
-    y += 1;
+    #[test]
+    fn single_row_test() {
+        use Neighborhood::*;
 
-    while y < NUMBER_OF_ROWS - 1 && map[y][x] == Tile::Floor {
-        y += 1;
+        let map: Map = "#L#.#".parse().unwrap();
+        assert_eq!(2, occupied_neighbors(&map, 0, 1, Adjacent));
+        assert_eq!(2, occupied_neighbors(&map, 0, 1, LineOfSight));
+        assert_eq!(0, occupied_neighbors(&map, 0, 4, Adjacent));
+        assert_eq!(1, occupied_neighbors(&map, 0, 4, LineOfSight));
+
+        let map: Map = "#\n.\n#".parse().unwrap();
+        assert_eq!(0, occupied_neighbors(&map, 0, 0, Adjacent));
+        assert_eq!(1, occupied_neighbors(&map, 0, 0, LineOfSight));
     }
 
-    u8::from(&map[y][x])
-}
+    #[test]
+    fn rules_test() {
+        let map: Map = include_str!("../test-input.txt").parse().unwrap();
+        assert_eq!(37, simulate(&map, &PART1_RULES).unwrap().occupied_seats());
+        assert_eq!(26, simulate(&map, &PART2_RULES).unwrap().occupied_seats());
+
+        let map: Map = "#.#.#\n.....\n#.#.#".parse().unwrap();
+        assert_eq!(0, occupied_neighbors(&map, 0, 2, Neighborhood::VonNeumann));
+        assert_eq!(5, occupied_neighbors(&map, 0, 2, Neighborhood::Sight(2)));
+        assert_eq!(0, occupied_neighbors(&map, 0, 2, Neighborhood::Sight(1)));
 
-fn down_right_neighbor(map: &[Vec<Tile>], mut y: usize, mut x: usize) -> u8 {
-    if y == NUMBER_OF_ROWS - 1 || x == NUMBER_OF_COLUMNS - 1 {
-        return 0;
+        let map: Map = "#...L".parse().unwrap();
+        assert_eq!(0, occupied_neighbors(&map, 0, 4, Neighborhood::Sight(3)));
+        assert_eq!(1, occupied_neighbors(&map, 0, 4, Neighborhood::LineOfSight));
     }
 
-    y += 1;
-    x += 1;
+    #[test]
+    fn step_test() {
+        let map: Map = include_str!("../test-input.txt").parse().unwrap();
+        let mut simulation = Simulation::new(&map, &PART1_RULES);
 
-    while y < NUMBER_OF_ROWS - 1 && x < NUMBER_OF_COLUMNS - 1 && map[y][x] == Tile::Floor {
-        y += 1;
-        x += 1;
+        assert_eq!(71, simulation.step().len());
+        assert_eq!(71, simulation.map.occupied_seats());
+
+        assert_eq!(51, simulation.step().len());
+        assert_eq!(20, simulation.map.occupied_seats());
     }
 
-    u8::from(&map[y][x])
+    #[test]
+    fn parallel_test() {
+        let map: Map = include_str!("../input.txt").parse().unwrap();
+
+        for rules in [PART1_RULES, PART2_RULES].iter() {
+            let expected = simulate(&map, rules);
+            assert_eq!(expected, simulate_serial(&map, rules));
+            assert_eq!(expected, simulate_parallel(&map, rules, 4));
+            assert_eq!(expected, simulate_parallel(&map, rules, 200));
+        }
+    }
+
+    #[test]
+    fn oscillation_test() {
+        // Both seats fill up, see each other and leave, over and over
+        let map: Map = "LL".parse().unwrap();
+        let rules = Rules {
+            neighborhood: Neighborhood::VonNeumann,
+            sit_at_most: 0,
+            leave_at_least: 1,
+        };
+
+        let expected = Err(Oscillation { generation: 2 });
+        assert_eq!(expected, simulate(&map, &rules));
+        assert_eq!(expected, simulate_serial(&map, &rules));
+        assert_eq!(expected, simulate_parallel(&map, &rules, 2));
+
+        let args =
+            |args: &[&str]| -> Vec<String> { args.iter().map(|arg| arg.to_string()).collect() };
+        assert!(parse_options(&args(&["--sit", "3", "--leave", "2"])).is_err());
+        assert!(parse_options(&args(&["--sit", "8", "--leave", "0"])).is_err());
+        assert!(parse_options(&args(&["--sit", "0", "--leave", "1"])).is_ok());
+    }
 }
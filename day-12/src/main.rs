@@ -1,7 +1,31 @@
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
 // static INPUT: &str = include_str!("../test-input.txt");
 static INPUT: &str = include_str!("../input.txt");
 
-#[derive(Debug, Default)]
+#[derive(Debug, PartialEq)]
+enum NavError {
+    UnknownAction(String),
+    InvalidValue(String),
+    InvalidAngle(i64),
+}
+
+impl fmt::Display for NavError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use NavError::*;
+
+        match self {
+            UnknownAction(action) => write!(f, "unknown action: {}", action),
+            InvalidValue(value) => write!(f, "invalid value: {}", value),
+            InvalidAngle(degrees) => write!(f, "can't turn {} degrees", degrees),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 struct Point {
     x: i64,
     y: i64,
@@ -26,83 +50,791 @@ impl Point {
         }
     }
 
-    fn rotate(&mut self, degrees: i64) -> Self {
-        match degrees {
-            90 => Point {
+    // Clockwise by any multiple of 90 degrees, including negative ones
+    fn rotate(&self, degrees: i64) -> Result<Self, NavError> {
+        match degrees.rem_euclid(360) {
+            0 => Ok(Point {
+                x: self.x,
+                y: self.y,
+            }),
+            90 => Ok(Point {
                 x: self.y,
                 y: -self.x,
-            },
-            180 => Point {
+            }),
+            180 => Ok(Point {
                 x: -self.x,
                 y: -self.y,
-            },
-            270 => Point {
+            }),
+            270 => Ok(Point {
                 x: -self.y,
                 y: self.x,
-            },
-            _ => panic!(),
+            }),
+            _ => Err(NavError::InvalidAngle(degrees)),
         }
     }
 }
 
-fn main() {
-    part1();
-    part2();
+// For turning by angles that aren't multiples of 90
+#[derive(Debug, Default, Clone, PartialEq)]
+struct FloatPoint {
+    x: f64,
+    y: f64,
+}
+
+impl FloatPoint {
+    fn manhattan_distance(&self) -> f64 {
+        self.x.abs() + self.y.abs()
+    }
+
+    fn add_scaled(&self, other: &Self, multiplier: f64) -> Self {
+        Self {
+            x: self.x + other.x * multiplier,
+            y: self.y + other.y * multiplier,
+        }
+    }
+
+    // Clockwise, like Point::rotate
+    fn rotate(&self, degrees: f64) -> Self {
+        let (sin, cos) = (-degrees).to_radians().sin_cos();
+
+        Self {
+            x: self.x * cos - self.y * sin,
+            y: self.x * sin + self.y * cos,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum NavInstruction {
+    North(i64),
+    South(i64),
+    East(i64),
+    West(i64),
+    Left(i64),
+    Right(i64),
+    Forward(i64),
 }
 
-fn part1() {
-    let mut facing: i64 = 0;
-    let mut position = Point::default();
+impl FromStr for NavInstruction {
+    type Err = NavError;
 
-    for instruction in INPUT.lines() {
-        let (action, value) = instruction.split_at(1);
-        let value: i64 = value.parse().unwrap();
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        use NavInstruction::*;
+
+        let mut chars = string.chars();
+        let action = chars.next().unwrap_or_default();
+        let value = chars.as_str();
+        let value = value
+            .parse()
+            .map_err(|_| NavError::InvalidValue(value.to_string()))?;
 
         match action {
-            "N" => position.y += value,
-            "S" => position.y -= value,
-            "E" => position.x += value,
-            "W" => position.x -= value,
-            "L" => facing += value,
-            "R" => facing += 360 - value,
-            "F" => {
-                match facing % 360 {
-                    0 => position.x += value,   // West
-                    90 => position.y += value,  // North
-                    180 => position.x -= value, // East
-                    270 => position.y -= value, // South
-                    _ => panic!(),
-                }
+            'N' => Ok(North(value)),
+            'S' => Ok(South(value)),
+            'E' => Ok(East(value)),
+            'W' => Ok(West(value)),
+            'L' => Ok(Left(value)),
+            'R' => Ok(Right(value)),
+            'F' => Ok(Forward(value)),
+            _ => Err(NavError::UnknownAction(action.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for NavInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use NavInstruction::*;
+
+        match self {
+            North(value) => write!(f, "N{}", value),
+            South(value) => write!(f, "S{}", value),
+            East(value) => write!(f, "E{}", value),
+            West(value) => write!(f, "W{}", value),
+            Left(degrees) => write!(f, "L{}", degrees),
+            Right(degrees) => write!(f, "R{}", degrees),
+            Forward(value) => write!(f, "F{}", value),
+        }
+    }
+}
+
+fn parse_instructions(input: &str) -> Result<Vec<NavInstruction>, NavError> {
+    input.lines().map(str::parse).collect()
+}
+
+// One way of interpreting the navigation instructions
+trait Navigator: Clone {
+    type Distance: fmt::Display;
+
+    fn apply(&mut self, instruction: NavInstruction) -> Result<(), NavError>;
+    fn manhattan_distance(&self) -> Self::Distance;
+
+    // Where the ship is and, for navigators that have one, where the
+    // waypoint is relative to the ship
+    fn position(&self) -> (f64, f64);
+    fn waypoint(&self) -> Option<(f64, f64)> {
+        None
+    }
+}
+
+fn navigate<N: Navigator>(
+    mut navigator: N,
+    instructions: &[NavInstruction],
+) -> Result<N::Distance, NavError> {
+    for &instruction in instructions {
+        navigator.apply(instruction)?;
+    }
+
+    Ok(navigator.manhattan_distance())
+}
+
+// Undoes the instructions in reverse order. Turning around before and after
+// going forward undoes F under both interpretations, so the result puts the
+// ship and its heading or waypoint back where they started either way.
+fn inverse(instructions: &[NavInstruction]) -> Vec<NavInstruction> {
+    use NavInstruction::*;
+
+    let mut inverse = Vec::with_capacity(instructions.len());
+
+    for &instruction in instructions.iter().rev() {
+        match instruction {
+            North(value) => inverse.push(South(value)),
+            South(value) => inverse.push(North(value)),
+            East(value) => inverse.push(West(value)),
+            West(value) => inverse.push(East(value)),
+            Left(degrees) => inverse.push(Right(degrees)),
+            Right(degrees) => inverse.push(Left(degrees)),
+            Forward(value) => inverse.extend_from_slice(&[Right(180), Forward(value), Right(180)]),
+        }
+    }
+
+    inverse
+}
+
+// Moves along one axis with whichever of the two directions fits the sign
+fn shift(
+    delta: i64,
+    positive: fn(i64) -> NavInstruction,
+    negative: fn(i64) -> NavInstruction,
+) -> Option<NavInstruction> {
+    match delta {
+        0 => None,
+        delta if delta > 0 => Some(positive(delta)),
+        delta => Some(negative(-delta)),
+    }
+}
+
+// Navigators that can work out the shortest list of N/S/E/W/F instructions
+// to get the ship from where it is to target
+trait Planner {
+    fn plan(&self, target: &Point) -> Vec<NavInstruction>;
+}
+
+impl Planner for Ship {
+    // F only ever goes along one axis, same as N/S/E/W, so it never helps
+    fn plan(&self, target: &Point) -> Vec<NavInstruction> {
+        use NavInstruction::*;
+
+        shift(target.x - self.position.x, East, West)
+            .into_iter()
+            .chain(shift(target.y - self.position.y, North, South))
+            .collect()
+    }
+}
+
+impl Planner for WaypointShip {
+    // The ship only moves by whole multiples of the waypoint, so the options
+    // are: F on its own, moving the waypoint along one axis then F, or
+    // setting the waypoint to exactly where we need to go and F1
+    fn plan(&self, target: &Point) -> Vec<NavInstruction> {
+        use NavInstruction::*;
+
+        let delta = Point {
+            x: target.x - self.position.x,
+            y: target.y - self.position.y,
+        };
+        let waypoint = &self.waypoint;
+
+        if delta == Point::default() {
+            return vec![];
+        }
+
+        // How many times `step` fits into `distance` going forwards, if exactly
+        let times = |distance: i64, step: i64| match step {
+            0 => None,
+            step if distance % step == 0 && distance / step > 0 => Some(distance / step),
+            _ => None,
+        };
+
+        let forward = match waypoint.x {
+            0 => times(delta.y, waypoint.y),
+            _ => times(delta.x, waypoint.x),
+        };
+        if let Some(k) = forward.filter(|&k| waypoint.multiply(k) == delta) {
+            return vec![Forward(k)];
+        }
+
+        let keep_x = match (waypoint.x, delta.x) {
+            (0, 0) => Some(1),
+            _ => times(delta.x, waypoint.x),
+        };
+        if let Some(k) = keep_x.filter(|&k| delta.y % k == 0) {
+            let shift = shift(delta.y / k - waypoint.y, North, South);
+            return shift.into_iter().chain(Some(Forward(k))).collect();
+        }
+
+        let keep_y = match (waypoint.y, delta.y) {
+            (0, 0) => Some(1),
+            _ => times(delta.y, waypoint.y),
+        };
+        if let Some(k) = keep_y.filter(|&k| delta.x % k == 0) {
+            let shift = shift(delta.x / k - waypoint.x, East, West);
+            return shift.into_iter().chain(Some(Forward(k))).collect();
+        }
+
+        shift(delta.x - waypoint.x, East, West)
+            .into_iter()
+            .chain(shift(delta.y - waypoint.y, North, South))
+            .chain(Some(Forward(1)))
+            .collect()
+    }
+}
+
+// The state of the navigator before any instructions and after each one
+struct RouteStep {
+    position: (f64, f64),
+    waypoint: Option<(f64, f64)>,
+}
+
+fn trace<N: Navigator>(
+    mut navigator: N,
+    instructions: &[NavInstruction],
+) -> Result<Vec<RouteStep>, NavError> {
+    let mut route = Vec::with_capacity(instructions.len() + 1);
+    let mut record = |navigator: &N| {
+        route.push(RouteStep {
+            position: navigator.position(),
+            waypoint: navigator.waypoint(),
+        })
+    };
+
+    record(&navigator);
+    for &instruction in instructions {
+        navigator.apply(instruction)?;
+        record(&navigator);
+    }
+
+    Ok(route)
+}
+
+// Draws the ship's path from the start (green) to the end (red), with each
+// waypoint as a thin blue line from the ship. North is up.
+fn to_svg(route: &[RouteStep]) -> String {
+    let points: Vec<(f64, f64)> = route
+        .iter()
+        .flat_map(|step| {
+            let (x, y) = step.position;
+            let waypoint = step.waypoint.map(|(dx, dy)| (x + dx, y + dy));
+            std::iter::once(step.position).chain(waypoint)
+        })
+        .collect();
+
+    let min_x = points.iter().map(|p| p.0).fold(0.0, f64::min);
+    let max_x = points.iter().map(|p| p.0).fold(0.0, f64::max);
+    let min_y = points.iter().map(|p| p.1).fold(0.0, f64::min);
+    let max_y = points.iter().map(|p| p.1).fold(0.0, f64::max);
+    let margin = ((max_x - min_x).max(max_y - min_y) * 0.05).max(1.0);
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\" width=\"800\" height=\"800\">\n",
+        min_x - margin,
+        -max_y - margin,
+        max_x - min_x + 2.0 * margin,
+        max_y - min_y + 2.0 * margin
+    );
+
+    for step in route {
+        if let Some((dx, dy)) = step.waypoint {
+            let (x, y) = step.position;
+            svg.push_str(&format!(
+                "  <line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"steelblue\" stroke-opacity=\"0.4\" stroke-width=\"1\" vector-effect=\"non-scaling-stroke\"/>\n",
+                x,
+                -y,
+                x + dx,
+                -(y + dy)
+            ));
+        }
+    }
+
+    let path: Vec<String> = route
+        .iter()
+        .map(|step| format!("{},{}", step.position.0, -step.position.1))
+        .collect();
+    svg.push_str(&format!(
+        "  <polyline points=\"{}\" fill=\"none\" stroke=\"black\" stroke-width=\"2\" vector-effect=\"non-scaling-stroke\"/>\n",
+        path.join(" ")
+    ));
+
+    let radius = margin / 2.0;
+    if let (Some(start), Some(end)) = (route.first(), route.last()) {
+        for (step, color) in [(start, "green"), (end, "red")].iter() {
+            svg.push_str(&format!(
+                "  <circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\"/>\n",
+                step.position.0, -step.position.1, radius, color
+            ));
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// Part 1: N/S/E/W move the ship and F moves it in the direction it's facing
+#[derive(Clone)]
+struct Ship {
+    position: Point,
+    facing: Point,
+}
+
+impl Default for Ship {
+    fn default() -> Self {
+        Self {
+            position: Point::default(),
+            facing: Point { x: 1, y: 0 },
+        }
+    }
+}
+
+impl Navigator for Ship {
+    type Distance = i64;
+
+    fn apply(&mut self, instruction: NavInstruction) -> Result<(), NavError> {
+        use NavInstruction::*;
+
+        match instruction {
+            North(value) => self.position.y += value,
+            South(value) => self.position.y -= value,
+            East(value) => self.position.x += value,
+            West(value) => self.position.x -= value,
+            Left(degrees) => {
+                self.facing = self
+                    .facing
+                    .rotate(-degrees)
+                    .map_err(|_| NavError::InvalidAngle(degrees))?
             }
-            _ => panic!(),
+            Right(degrees) => self.facing = self.facing.rotate(degrees)?,
+            Forward(value) => self.position = self.position.add(&self.facing.multiply(value)),
         }
+
+        Ok(())
+    }
+
+    fn manhattan_distance(&self) -> i64 {
+        self.position.manhattan_distance()
+    }
+
+    fn position(&self) -> (f64, f64) {
+        (self.position.x as f64, self.position.y as f64)
     }
+}
 
-    println!("part1: {}", position.manhattan_distance());
+// Part 2: N/S/E/W move the waypoint, which is relative to the ship, and F
+// moves the ship towards the waypoint
+#[derive(Clone)]
+struct WaypointShip {
+    position: Point,
+    waypoint: Point,
 }
 
-fn part2() {
-    let mut position = Point::default();
-    let mut waypoint = Point { x: 10, y: 1 };
+impl Default for WaypointShip {
+    fn default() -> Self {
+        Self {
+            position: Point::default(),
+            waypoint: Point { x: 10, y: 1 },
+        }
+    }
+}
 
-    for instruction in INPUT.lines() {
-        let (action, value) = instruction.split_at(1);
-        let value: i64 = value.parse().unwrap();
+impl Navigator for WaypointShip {
+    type Distance = i64;
 
-        match action {
-            "N" => waypoint.y += value,
-            "S" => waypoint.y -= value,
-            "E" => waypoint.x += value,
-            "W" => waypoint.x -= value,
-            "R" => waypoint = waypoint.rotate(value),
-            "L" => waypoint = waypoint.rotate(360 - value),
-            "F" => {
-                let vector = waypoint.multiply(value);
-                position = position.add(&vector);
+    fn apply(&mut self, instruction: NavInstruction) -> Result<(), NavError> {
+        use NavInstruction::*;
+
+        match instruction {
+            North(value) => self.waypoint.y += value,
+            South(value) => self.waypoint.y -= value,
+            East(value) => self.waypoint.x += value,
+            West(value) => self.waypoint.x -= value,
+            Left(degrees) => {
+                self.waypoint = self
+                    .waypoint
+                    .rotate(-degrees)
+                    .map_err(|_| NavError::InvalidAngle(degrees))?
             }
-            _ => panic!(),
+            Right(degrees) => self.waypoint = self.waypoint.rotate(degrees)?,
+            Forward(value) => self.position = self.position.add(&self.waypoint.multiply(value)),
+        }
+
+        Ok(())
+    }
+
+    fn manhattan_distance(&self) -> i64 {
+        self.position.manhattan_distance()
+    }
+
+    fn position(&self) -> (f64, f64) {
+        (self.position.x as f64, self.position.y as f64)
+    }
+
+    fn waypoint(&self) -> Option<(f64, f64)> {
+        Some((self.waypoint.x as f64, self.waypoint.y as f64))
+    }
+}
+
+// Same as Ship but can turn by any angle
+#[derive(Clone)]
+struct FloatShip {
+    position: FloatPoint,
+    facing: FloatPoint,
+}
+
+impl Default for FloatShip {
+    fn default() -> Self {
+        Self {
+            position: FloatPoint::default(),
+            facing: FloatPoint { x: 1.0, y: 0.0 },
+        }
+    }
+}
+
+impl Navigator for FloatShip {
+    type Distance = f64;
+
+    fn apply(&mut self, instruction: NavInstruction) -> Result<(), NavError> {
+        use NavInstruction::*;
+
+        match instruction {
+            North(value) => self.position.y += value as f64,
+            South(value) => self.position.y -= value as f64,
+            East(value) => self.position.x += value as f64,
+            West(value) => self.position.x -= value as f64,
+            Left(degrees) => self.facing = self.facing.rotate(-degrees as f64),
+            Right(degrees) => self.facing = self.facing.rotate(degrees as f64),
+            Forward(value) => self.position = self.position.add_scaled(&self.facing, value as f64),
+        }
+
+        Ok(())
+    }
+
+    fn manhattan_distance(&self) -> f64 {
+        self.position.manhattan_distance()
+    }
+
+    fn position(&self) -> (f64, f64) {
+        (self.position.x, self.position.y)
+    }
+}
+
+// Same as WaypointShip but can turn by any angle
+#[derive(Clone)]
+struct FloatWaypointShip {
+    position: FloatPoint,
+    waypoint: FloatPoint,
+}
+
+impl Default for FloatWaypointShip {
+    fn default() -> Self {
+        Self {
+            position: FloatPoint::default(),
+            waypoint: FloatPoint { x: 10.0, y: 1.0 },
+        }
+    }
+}
+
+impl Navigator for FloatWaypointShip {
+    type Distance = f64;
+
+    fn apply(&mut self, instruction: NavInstruction) -> Result<(), NavError> {
+        use NavInstruction::*;
+
+        match instruction {
+            North(value) => self.waypoint.y += value as f64,
+            South(value) => self.waypoint.y -= value as f64,
+            East(value) => self.waypoint.x += value as f64,
+            West(value) => self.waypoint.x -= value as f64,
+            Left(degrees) => self.waypoint = self.waypoint.rotate(-degrees as f64),
+            Right(degrees) => self.waypoint = self.waypoint.rotate(degrees as f64),
+            Forward(value) => {
+                self.position = self.position.add_scaled(&self.waypoint, value as f64)
+            }
+        }
+
+        Ok(())
+    }
+
+    fn manhattan_distance(&self) -> f64 {
+        self.position.manhattan_distance()
+    }
+
+    fn position(&self) -> (f64, f64) {
+        (self.position.x, self.position.y)
+    }
+
+    fn waypoint(&self) -> Option<(f64, f64)> {
+        Some((self.waypoint.x, self.waypoint.y))
+    }
+}
+
+// usage: day-12 [--float] [--trace] [--svg DIR] [--return] [--plan X,Y]
+fn main() {
+    let mut float = false;
+    let mut print_trace = false;
+    let mut svg_dir = None;
+    let mut print_return = false;
+    let mut plan_target = None;
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--float" => float = true,
+            "--trace" => print_trace = true,
+            "--svg" => svg_dir = Some(PathBuf::from(args.next().unwrap_or_else(|| usage()))),
+            "--return" => print_return = true,
+            "--plan" => match args.next().as_deref().and_then(parse_point) {
+                Some(target) => plan_target = Some(target),
+                None => usage(),
+            },
+            _ => usage(),
+        }
+    }
+
+    if let Some(target) = plan_target {
+        let plan = Ship::default().plan(&target);
+        println!("part1 plan: {}", format_instructions(&plan));
+
+        let plan = WaypointShip::default().plan(&target);
+        println!("part2 plan: {}", format_instructions(&plan));
+        return;
+    }
+
+    let instructions = match parse_instructions(INPUT) {
+        Ok(instructions) => instructions,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    if print_return {
+        println!("return: {}", format_instructions(&inverse(&instructions)));
+        return;
+    }
+
+    let output = Output {
+        print_trace,
+        svg_dir,
+    };
+
+    if float {
+        run("part1", FloatShip::default(), &instructions, &output);
+        run(
+            "part2",
+            FloatWaypointShip::default(),
+            &instructions,
+            &output,
+        );
+    } else {
+        run("part1", Ship::default(), &instructions, &output);
+        run("part2", WaypointShip::default(), &instructions, &output);
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("usage: day-12 [--float] [--trace] [--svg DIR] [--return] [--plan X,Y]");
+    std::process::exit(1);
+}
+
+fn parse_point(string: &str) -> Option<Point> {
+    let mut coordinates = string
+        .split(',')
+        .map(|coordinate| coordinate.trim().parse());
+
+    match (coordinates.next(), coordinates.next(), coordinates.next()) {
+        (Some(Ok(x)), Some(Ok(y)), None) => Some(Point { x, y }),
+        _ => None,
+    }
+}
+
+fn format_instructions(instructions: &[NavInstruction]) -> String {
+    let instructions: Vec<String> = instructions.iter().map(ToString::to_string).collect();
+    instructions.join(" ")
+}
+
+struct Output {
+    print_trace: bool,
+    svg_dir: Option<PathBuf>,
+}
+
+fn run<N: Navigator>(part: &str, navigator: N, instructions: &[NavInstruction], output: &Output) {
+    if output.print_trace || output.svg_dir.is_some() {
+        if let Err(err) = export(part, &navigator, instructions, output) {
+            println!("{}: {}", part, err);
+            return;
         }
     }
 
-    println!("part2: {}", position.manhattan_distance());
+    match navigate(navigator, instructions) {
+        // Precision is ignored for the integer navigators
+        Ok(distance) => println!("{}: {:.3}", part, distance),
+        Err(err) => println!("{}: {}", part, err),
+    }
+}
+
+fn export<N: Navigator>(
+    part: &str,
+    navigator: &N,
+    instructions: &[NavInstruction],
+    output: &Output,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let route = trace(navigator.clone(), instructions).map_err(|err| err.to_string())?;
+
+    if output.print_trace {
+        for step in route.iter() {
+            match step.waypoint {
+                Some(waypoint) => println!("{}: {:?} waypoint {:?}", part, step.position, waypoint),
+                None => println!("{}: {:?}", part, step.position),
+            }
+        }
+    }
+
+    if let Some(dir) = &output.svg_dir {
+        fs::create_dir_all(dir)?;
+        fs::write(dir.join(format!("{}.svg", part)), to_svg(&route))?;
+    }
+
+    Ok(())
+}
+
+mod test {
+    #[allow(unused)]
+    use super::*;
+
+    #[test]
+    fn rotate_test() {
+        let point = Point { x: 10, y: 4 };
+
+        assert_eq!(Ok(Point { x: 4, y: -10 }), point.rotate(90));
+        assert_eq!(Ok(Point { x: 10, y: 4 }), point.rotate(0));
+        assert_eq!(Ok(Point { x: 10, y: 4 }), point.rotate(360));
+        assert_eq!(Ok(Point { x: -4, y: 10 }), point.rotate(-90));
+        assert_eq!(Ok(Point { x: -10, y: -4 }), point.rotate(540));
+        assert_eq!(Err(NavError::InvalidAngle(45)), point.rotate(45));
+
+        let point = FloatPoint { x: 10.0, y: 4.0 }.rotate(90.0);
+        assert!((point.x - 4.0).abs() < 1e-9 && (point.y + 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn navigate_test() {
+        let instructions = parse_instructions(include_str!("../test-input.txt")).unwrap();
+        assert_eq!(Ok(25), navigate(Ship::default(), &instructions));
+        assert_eq!(Ok(286), navigate(WaypointShip::default(), &instructions));
+
+        let distance = navigate(FloatWaypointShip::default(), &instructions).unwrap();
+        assert!((distance - 286.0).abs() < 1e-9);
+
+        let instructions = parse_instructions("L450\nR-180\nF10").unwrap();
+        assert_eq!(Ok(10), navigate(Ship::default(), &instructions));
+
+        let instructions = parse_instructions("L45").unwrap();
+        assert_eq!(
+            Err(NavError::InvalidAngle(45)),
+            navigate(Ship::default(), &instructions)
+        );
+
+        assert_eq!(
+            Err(NavError::UnknownAction("X".to_string())),
+            parse_instructions("X5")
+        );
+        assert_eq!(
+            Err(NavError::InvalidValue("x".to_string())),
+            parse_instructions("Fx")
+        );
+    }
+
+    #[test]
+    fn trace_test() {
+        let instructions = parse_instructions(include_str!("../test-input.txt")).unwrap();
+
+        let route = trace(Ship::default(), &instructions).unwrap();
+        let positions: Vec<(f64, f64)> = route.iter().map(|step| step.position).collect();
+        assert_eq!(
+            vec![
+                (0.0, 0.0),
+                (10.0, 0.0),
+                (10.0, 3.0),
+                (17.0, 3.0),
+                (17.0, 3.0),
+                (17.0, -8.0)
+            ],
+            positions
+        );
+        assert!(route.iter().all(|step| step.waypoint.is_none()));
+
+        let route = trace(WaypointShip::default(), &instructions).unwrap();
+        assert_eq!(Some((10.0, 1.0)), route[0].waypoint);
+        assert_eq!((214.0, -72.0), route[5].position);
+        assert_eq!(Some((4.0, -10.0)), route[5].waypoint);
+
+        let svg = to_svg(&route);
+        assert!(svg.starts_with("<svg"));
+        assert_eq!(6, svg.matches("<line").count());
+        assert_eq!(2, svg.matches("<circle").count());
+    }
+
+    #[test]
+    fn inverse_test() {
+        let mut instructions = parse_instructions(include_str!("../input.txt")).unwrap();
+        instructions.extend(inverse(&instructions));
+
+        let route = trace(Ship::default(), &instructions).unwrap();
+        assert_eq!((0.0, 0.0), route.last().unwrap().position);
+
+        let route = trace(WaypointShip::default(), &instructions).unwrap();
+        assert_eq!((0.0, 0.0), route.last().unwrap().position);
+        assert_eq!(Some((10.0, 1.0)), route.last().unwrap().waypoint);
+    }
+
+    #[test]
+    fn plan_test() {
+        use NavInstruction::*;
+
+        let ship = WaypointShip::default();
+        let plan = |x, y| ship.plan(&Point { x, y });
+
+        assert_eq!(Vec::<NavInstruction>::new(), plan(0, 0));
+        assert_eq!(vec![Forward(3)], plan(30, 3));
+        assert_eq!(vec![North(2), Forward(3)], plan(30, 9));
+        assert_eq!(vec![East(2), Forward(5)], plan(60, 5));
+        assert_eq!(vec![West(13), South(8), Forward(1)], plan(-3, -7));
+        assert_eq!(vec![East(5), Forward(2)], plan(30, 2));
+
+        let vertical = WaypointShip {
+            position: Point::default(),
+            waypoint: Point { x: 0, y: 5 },
+        };
+        assert_eq!(vec![Forward(2)], vertical.plan(&Point { x: 0, y: 10 }));
+
+        for x in -25..25 {
+            for y in -25..25 {
+                let target = Point { x, y };
+                let route = trace(WaypointShip::default(), &ship.plan(&target)).unwrap();
+                assert_eq!((x as f64, y as f64), route.last().unwrap().position);
+
+                let plan = Ship::default().plan(&target);
+                let route = trace(Ship::default(), &plan).unwrap();
+                assert_eq!((x as f64, y as f64), route.last().unwrap().position);
+            }
+        }
+    }
 }
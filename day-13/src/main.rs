@@ -1,60 +1,440 @@
+use std::fmt;
+use std::str::FromStr;
+
 static INPUT: &str = include_str!("../input.txt");
 
+#[derive(Debug, PartialEq, Eq)]
+enum ScheduleError {
+    MissingLine,
+    InvalidTimestamp(String),
+    InvalidBus(String),
+    NoBuses,
+}
+
+impl fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ScheduleError::*;
+
+        match self {
+            MissingLine => write!(f, "expected a timestamp line and a bus line"),
+            InvalidTimestamp(timestamp) => write!(f, "invalid timestamp: {}", timestamp),
+            InvalidBus(bus) => write!(f, "invalid bus id: {}", bus),
+            NoBuses => write!(f, "no buses in service"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Bus {
+    offset: usize,
+    id: usize,
+}
+
+// The first line (our earliest departure) can be left blank if we only care
+// about the buses
+#[derive(Debug, PartialEq, Eq)]
+struct Schedule {
+    timestamp: Option<usize>,
+    buses: Vec<Bus>,
+}
+
+impl FromStr for Schedule {
+    type Err = ScheduleError;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        let mut lines = string.lines();
+        let timestamp = lines.next().ok_or(ScheduleError::MissingLine)?.trim();
+        let buses = lines.next().ok_or(ScheduleError::MissingLine)?.trim();
+
+        let timestamp = match timestamp {
+            "" => None,
+            timestamp => Some(
+                timestamp
+                    .parse()
+                    .map_err(|_| ScheduleError::InvalidTimestamp(timestamp.to_string()))?,
+            ),
+        };
+
+        let mut schedule = Schedule {
+            timestamp,
+            buses: vec![],
+        };
+
+        for (offset, id) in buses.split(',').enumerate() {
+            if id == "x" {
+                continue;
+            }
+
+            match id.parse() {
+                Ok(id) if id > 0 => schedule.buses.push(Bus { offset, id }),
+                _ => return Err(ScheduleError::InvalidBus(id.to_string())),
+            }
+        }
+
+        if schedule.buses.is_empty() {
+            return Err(ScheduleError::NoBuses);
+        }
+
+        Ok(schedule)
+    }
+}
+
+impl Bus {
+    // The first n times this bus leaves at or after `time`
+    fn departures(&self, time: usize, n: usize) -> Vec<usize> {
+        let first = time.div_ceil(self.id) * self.id;
+        (0..n).map(|i| first + i * self.id).collect()
+    }
+}
+
+impl Schedule {
+    // return type: (bus id, minutes to wait) for the first bus we can catch
+    fn earliest_bus(&self) -> Option<(usize, usize)> {
+        let timestamp = self.timestamp?;
+
+        self.buses
+            .iter()
+            .map(|bus| (bus.id, bus.departures(timestamp, 1)[0] - timestamp))
+            .min_by_key(|&(_, wait)| wait)
+    }
+
+    // return type: [(bus id, departures)] for every bus
+    fn next_departures(&self, time: usize, n: usize) -> Vec<(usize, Vec<usize>)> {
+        self.buses
+            .iter()
+            .map(|bus| (bus.id, bus.departures(time, n)))
+            .collect()
+    }
+
+    fn alignment(&self) -> Result<Alignment, CrtError> {
+        align(&self.buses)
+    }
+}
+
+// The buses line up at `first` and then every `period` minutes after that
+#[derive(Debug, PartialEq, Eq)]
+struct Alignment {
+    first: i128,
+    period: i128,
+}
+
+impl Alignment {
+    // The first time they line up at or after `time`
+    fn next_after(&self, time: i128) -> i128 {
+        if time <= self.first {
+            self.first
+        } else {
+            self.first + (time - self.first + self.period - 1) / self.period * self.period
+        }
+    }
+
+    fn is_aligned(&self, time: i128) -> bool {
+        time >= self.first && (time - self.first) % self.period == 0
+    }
+}
+
+// usage: day-13 [--next TIME N] [--align OFFSET:ID,... [--after TIME]]
+//               [--timetable [TIME [RADIUS]]]
 fn main() {
-    part1();
-    part2();
+    let schedule: Schedule = match INPUT.parse() {
+        Ok(schedule) => schedule,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    match args[..] {
+        [] => {
+            part1(&schedule);
+            part2(&schedule);
+        }
+        ["--next", time, n] => match (time.parse(), n.parse()) {
+            (Ok(time), Ok(n)) => {
+                for (id, departures) in schedule.next_departures(time, n) {
+                    println!("{}: {:?}", id, departures);
+                }
+            }
+            _ => usage(),
+        },
+        ["--align", buses] => print_alignment(buses, 0),
+        ["--align", buses, "--after", time] => match time.parse() {
+            Ok(time) => print_alignment(buses, time),
+            Err(_) => usage(),
+        },
+        ["--timetable"] => print_timetable(&schedule, None, None),
+        ["--timetable", time] => match time.parse() {
+            Ok(time) => print_timetable(&schedule, Some(time), None),
+            Err(_) => usage(),
+        },
+        ["--timetable", time, radius] => match (time.parse(), radius.parse()) {
+            (Ok(time), Ok(radius)) => print_timetable(&schedule, Some(time), Some(radius)),
+            _ => usage(),
+        },
+        _ => usage(),
+    }
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: day-13 [--next TIME N] [--align OFFSET:ID,... [--after TIME]] \
+         [--timetable [TIME [RADIUS]]]"
+    );
+    std::process::exit(1);
 }
 
-fn part1() {
-    let mut lines = INPUT.lines();
-    let timestamp: usize = lines.next().unwrap().parse().unwrap();
-    let (bus_id, until): (usize, usize) = lines
-        .next()
-        .unwrap()
+fn print_alignment(buses: &str, after: i128) {
+    let buses: Option<Vec<Bus>> = buses
         .split(',')
-        .filter_map(|id| id.parse::<usize>().ok())
         .map(|bus| {
-            let just_before = timestamp / bus * bus;
-            let next_bus = just_before + bus;
-            (bus, next_bus - timestamp)
+            let mut parts = bus.split(':').map(str::parse);
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some(Ok(offset)), Some(Ok(id)), None) if id > 0 => Some(Bus { offset, id }),
+                _ => None,
+            }
         })
-        .min_by(|&x, &y| x.1.cmp(&y.1))
-        .unwrap();
+        .collect();
 
-    println!("part1 = {}", bus_id * until);
+    match buses.map(|buses| align(&buses)) {
+        Some(Ok(alignment)) => println!(
+            "first at {}, then every {}",
+            alignment.next_after(after),
+            alignment.period
+        ),
+        Some(Err(err)) => println!("{}", err),
+        None => usage(),
+    }
 }
 
-fn part2() {
-    let mut pairs: Vec<(usize, usize)> = INPUT
-        .lines()
-        .nth(1)
-        .unwrap()
-        .split(',')
-        .enumerate()
-        .filter_map(|(i, id)| match id.parse() {
-            Ok(id) => Some((i, id)),
-            Err(_err) => None,
-        })
+// Without a time the window is centered on the alignment, wide enough to show
+// the last bus leaving
+fn print_timetable(schedule: &Schedule, time: Option<usize>, radius: Option<usize>) {
+    let alignment = schedule.alignment().ok();
+    let first = match &alignment {
+        Some(alignment) if alignment.first <= usize::MAX as i128 => Some(alignment.first as usize),
+        _ => None,
+    };
+
+    let time = match time.or(first) {
+        Some(time) => time,
+        None => usage(),
+    };
+    let radius =
+        radius.unwrap_or_else(|| schedule.buses.iter().map(|bus| bus.offset).max().unwrap() + 1);
+
+    print!(
+        "{}",
+        timetable(
+            schedule,
+            time.saturating_sub(radius),
+            time.saturating_add(radius),
+            alignment.as_ref()
+        )
+    );
+}
+
+// The D/. grid from the puzzle for times from..=to, one column per bus. The
+// rows where the buses line up are shown in reverse video.
+fn timetable(schedule: &Schedule, from: usize, to: usize, alignment: Option<&Alignment>) -> String {
+    let time_width = "time".len().max(to.to_string().len()) + 2;
+    let headers: Vec<String> = schedule
+        .buses
+        .iter()
+        .map(|bus| format!("bus {}", bus.id))
         .collect();
 
-    pairs.sort_by(|x, y| y.1.cmp(&x.1));
+    let mut s = format!(
+        "{:<width$}{}\n",
+        "time",
+        headers.join("  "),
+        width = time_width
+    );
+
+    for time in from..=to {
+        let mut row = format!("{:<width$}", time, width = time_width);
+        for (bus, header) in schedule.buses.iter().zip(headers.iter()) {
+            let cell = if time % bus.id == 0 { 'D' } else { '.' };
+            row.push_str(&format!("{:^width$}", cell, width = header.len()));
+            row.push_str("  ");
+        }
+        let row = row.trim_end();
+
+        if alignment.is_some_and(|alignment| alignment.is_aligned(time as i128)) {
+            s.push_str("\x1b[7m");
+            s.push_str(row);
+            s.push_str("\x1b[0m");
+        } else {
+            s.push_str(row);
+        }
+        s.push('\n');
+    }
 
-    let mut ts = pairs[0].1 - pairs[0].0;
+    s
+}
 
-    'outer: loop {
-        let mut jump_by = pairs[0].1;
+fn part1(schedule: &Schedule) {
+    match schedule.earliest_bus() {
+        Some((bus_id, until)) => println!("part1 = {}", bus_id * until),
+        None => println!("part1 = no timestamp in schedule"),
+    }
+}
 
-        for (i, id) in pairs.iter().skip(1) {
-            if (ts + i) % id != 0 {
-                ts += jump_by;
-                continue 'outer;
-            } else {
-                jump_by *= id;
-            }
+fn part2(schedule: &Schedule) {
+    match schedule.alignment() {
+        Ok(alignment) => println!("part2 = {}", alignment.first),
+        Err(err) => println!("part2 = {}", err),
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum CrtError {
+    NoSolution,
+    Overflow,
+}
+
+impl fmt::Display for CrtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CrtError::NoSolution => write!(f, "no solution"),
+            CrtError::Overflow => write!(f, "overflow"),
+        }
+    }
+}
+
+// return type: (gcd, x, y) such that a * x + b * y = gcd
+fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    if b == 0 {
+        (a, 1, 0)
+    } else {
+        let (gcd, x, y) = extended_gcd(b, a % b);
+        (gcd, y, x - a / b * y)
+    }
+}
+
+// Combines t = a1 (mod m1) and t = a2 (mod m2) into a single congruence
+// t = a (mod lcm(m1, m2)). The moduli don't need to be coprime, but if they
+// share a factor the two congruences have to agree modulo that factor.
+fn merge_congruences(
+    (a1, m1): (i128, i128),
+    (a2, m2): (i128, i128),
+) -> Result<(i128, i128), CrtError> {
+    let (gcd, inverse, _) = extended_gcd(m1, m2);
+    let difference = a2 - a1;
+
+    if difference % gcd != 0 {
+        return Err(CrtError::NoSolution);
+    }
+
+    let lcm = (m1 / gcd).checked_mul(m2).ok_or(CrtError::Overflow)?;
+
+    // m1 * k = a2 - a1 (mod m2), and inverse is m1's inverse mod m2 / gcd
+    let m2_reduced = m2 / gcd;
+    let k = (difference / gcd % m2_reduced)
+        .checked_mul(inverse % m2_reduced)
+        .ok_or(CrtError::Overflow)?
+        .rem_euclid(m2_reduced);
+    let a = (a1 + m1 * k).rem_euclid(lcm);
+
+    Ok((a, lcm))
+}
+
+// Earliest timestamp t such that each bus leaves at t + its offset, and how
+// often that happens
+fn align(buses: &[Bus]) -> Result<Alignment, CrtError> {
+    buses
+        .iter()
+        .map(|bus| {
+            let id = bus.id as i128;
+            ((-(bus.offset as i128)).rem_euclid(id), id)
+        })
+        .try_fold((0, 1), merge_congruences)
+        .map(|(first, period)| Alignment { first, period })
+}
+
+mod test {
+    #[allow(unused)]
+    use super::*;
+
+    #[allow(unused)]
+    fn buses(pairs: &[(usize, usize)]) -> Vec<Bus> {
+        pairs
+            .iter()
+            .map(|&(offset, id)| Bus { offset, id })
+            .collect()
+    }
+
+    #[test]
+    fn align_test() {
+        let inputs = [
+            (include_str!("../test-input1.txt"), 1068781),
+            (include_str!("../test-input2.txt"), 3417),
+            (include_str!("../test-input3.txt"), 754018),
+            (include_str!("../test-input4.txt"), 779210),
+            (include_str!("../test-input5.txt"), 1261476),
+            (include_str!("../test-input6.txt"), 1202161486),
+        ];
+
+        for &(input, expected) in inputs.iter() {
+            let schedule: Schedule = input.parse().unwrap();
+            assert_eq!(expected, schedule.alignment().unwrap().first);
         }
 
-        break;
+        // 4 and 6 share a factor of 2: t = 2 (mod 4) and t = 4 (mod 6)
+        let alignment = align(&buses(&[(2, 4), (2, 6)])).unwrap();
+        assert_eq!(
+            Alignment {
+                first: 10,
+                period: 12
+            },
+            alignment
+        );
+        assert_eq!(22, alignment.next_after(11));
+        assert_eq!(22, alignment.next_after(22));
+
+        // t = 0 (mod 4) and t = 1 (mod 6) can't both hold
+        assert_eq!(Err(CrtError::NoSolution), align(&buses(&[(0, 4), (5, 6)])));
+
+        let big = 1 << 62;
+        assert_eq!(
+            Err(CrtError::Overflow),
+            align(&buses(&[(0, big + 1), (0, big + 3), (0, big + 5)]))
+        );
+    }
+
+    #[test]
+    fn schedule_test() {
+        let schedule: Schedule = "939\n7,13,x,x,59,x,31,19\n".parse().unwrap();
+        assert_eq!(Some((59, 5)), schedule.earliest_bus());
+        assert_eq!(vec![944, 1003, 1062], schedule.next_departures(939, 3)[2].1);
+        assert_eq!(vec![938], schedule.next_departures(938, 1)[0].1);
+
+        assert_eq!(
+            Err(ScheduleError::InvalidBus("0".to_string())),
+            "939\n7,0\n".parse::<Schedule>()
+        );
+        assert_eq!(
+            Err(ScheduleError::NoBuses),
+            "939\nx,x\n".parse::<Schedule>()
+        );
     }
 
-    println!("part2 = {}", ts);
+    #[test]
+    fn timetable_test() {
+        let schedule: Schedule = "939\n7,13,x,x,59,x,31,19\n".parse().unwrap();
+        let expected = "\
+time  bus 7  bus 13  bus 59  bus 31  bus 19
+943     .      .       .       .       .
+\x1b[7m944     .      .       D       .       .\x1b[0m
+945     D      .       .       .       .
+";
+        // 944 is a later alignment, not the first
+        let alignment = Alignment {
+            first: 941,
+            period: 3,
+        };
+        assert_eq!(expected, timetable(&schedule, 943, 945, Some(&alignment)));
+    }
 }
@@ -1,260 +1,655 @@
-use std::collections::{HashMap, VecDeque};
-use std::convert::From;
+use std::collections::HashMap;
 use std::fmt;
 
 static INPUT: &str = include_str!("../input.txt");
 // static INPUT: &str = include_str!("../test-input.txt");
+static DEFAULT_WIDTH: u32 = 36;
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-enum Bit {
-    Zero,
-    One,
-    Copy,
+#[derive(Debug, PartialEq, Eq)]
+enum MaskError {
+    Width(u32),
+    Length { expected: u32, found: usize },
+    InvalidBit(char),
 }
 
-impl Default for Bit {
-    fn default() -> Self {
-        Self::Zero
-    }
-}
+impl fmt::Display for MaskError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use MaskError::*;
 
-impl From<char> for Bit {
-    fn from(c: char) -> Self {
-        use Bit::*;
-
-        match c {
-            '0' => Zero,
-            '1' => One,
-            'X' => Copy,
-            _ => {
-                eprintln!("bad mask bit: {}", c);
-                panic!();
+        match self {
+            Width(width) => write!(f, "word width must be between 1 and 64, not {}", width),
+            Length { expected, found } => {
+                write!(f, "expected a {} bit mask, found {} bits", expected, found)
             }
+            InvalidBit(c) => write!(f, "bad mask bit: {}", c),
         }
     }
 }
 
-impl From<Bit> for char {
-    fn from(bit: Bit) -> Self {
-        use Bit::*;
+// `ones` has a 1 wherever the mask has a 1 and `floating` wherever it has an
+// X; everything else within `width` bits is a 0
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Mask {
+    ones: u64,
+    floating: u64,
+    width: u32,
+}
 
-        match bit {
-            Zero => '0',
-            One => '1',
-            Copy => 'X',
+impl Mask {
+    fn parse(string: &str, width: u32) -> Result<Self, MaskError> {
+        if width == 0 || width > 64 {
+            return Err(MaskError::Width(width));
         }
-    }
-}
 
-impl From<usize> for Bit {
-    fn from(c: usize) -> Self {
-        use Bit::*;
+        if string.len() != width as usize {
+            return Err(MaskError::Length {
+                expected: width,
+                found: string.len(),
+            });
+        }
 
-        match c {
-            0 => Zero,
-            1 => One,
-            _ => {
-                eprintln!("bad mask bit: {}", c);
-                panic!();
+        let mut mask = Mask {
+            ones: 0,
+            floating: 0,
+            width,
+        };
+
+        for c in string.chars() {
+            mask.ones <<= 1;
+            mask.floating <<= 1;
+
+            match c {
+                '0' => (),
+                '1' => mask.ones |= 1,
+                'X' => mask.floating |= 1,
+                _ => return Err(MaskError::InvalidBit(c)),
             }
         }
+
+        Ok(mask)
     }
-}
 
-impl Bit {
-    fn or(&self, other: &Self) -> Self {
-        match *other {
-            Bit::Zero => Bit::Zero,
-            Bit::One => Bit::One,
-            Bit::Copy => *self,
+    // Leaves every bit of the word alone
+    fn identity(width: u32) -> Self {
+        Mask {
+            ones: 0,
+            floating: word(width),
+            width,
         }
     }
 
-    fn or2(&self, other: &Self) -> Self {
-        match *other {
-            Bit::Zero => *self,
-            Bit::One => Bit::One,
-            Bit::Copy => Bit::Copy,
+    // The mask as it's written in the program
+    fn bits(&self) -> String {
+        (0..self.width)
+            .rev()
+            .map(|i| {
+                if self.floating >> i & 1 == 1 {
+                    'X'
+                } else if self.ones >> i & 1 == 1 {
+                    '1'
+                } else {
+                    '0'
+                }
+            })
+            .collect()
+    }
+
+    // Version 1: 0s and 1s overwrite the value, Xs leave it alone
+    fn apply_to_value(&self, value: u64) -> u64 {
+        value & self.floating | self.ones
+    }
+
+    // Version 2: 1s overwrite the address, 0s leave it alone and Xs float
+    fn apply_to_address(&self, address: u64) -> Pattern {
+        Pattern {
+            fixed: (address & word(self.width) | self.ones) & !self.floating,
+            floating: self.floating,
         }
     }
 }
 
-#[derive(Debug, Copy, Clone)]
-struct Mask {
-    mask: [Bit; 36],
+impl fmt::Display for Mask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "M({})", self.bits())
+    }
+}
+
+// A mask with the low `width` bits set
+fn word(width: u32) -> u64 {
+    u64::MAX >> (64 - width)
 }
 
-impl From<usize> for Mask {
-    fn from(mut num: usize) -> Self {
-        let mut mask = [Bit::Zero; 36];
-        let mut i = 0;
+// A set of addresses: every bit in `floating` can be either 0 or 1 and every
+// other bit has to match `fixed` (which is 0 under the floating bits)
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Pattern {
+    fixed: u64,
+    floating: u64,
+}
+
+impl Pattern {
+    // u128 since a pattern can float all 64 bits
+    fn len(&self) -> u128 {
+        1 << self.floating.count_ones()
+    }
+
+    fn overlaps(&self, other: &Self) -> bool {
+        let both_fixed = !self.floating & !other.floating;
+        (self.fixed ^ other.fixed) & both_fixed == 0
+    }
+
+    // The addresses in self but not in other, as disjoint patterns. Each bit
+    // that floats here but is fixed in other splits off the half that
+    // disagrees with other; whatever is left at the end is inside other.
+    fn subtract(&self, other: &Self) -> Vec<Self> {
+        if !self.overlaps(other) {
+            return vec![*self];
+        }
+
+        let mut remaining = *self;
+        let mut difference = vec![];
+        let mut splits = self.floating & !other.floating;
 
-        while num > 0 {
-            let bit = Bit::from(num % 2);
-            mask[i] = bit;
+        while splits != 0 {
+            let bit = splits & splits.wrapping_neg();
+            splits &= !bit;
 
-            num >>= 1;
-            i += 1;
+            remaining.floating &= !bit;
+            difference.push(Pattern {
+                fixed: remaining.fixed | (!other.fixed & bit),
+                floating: remaining.floating,
+            });
+            remaining.fixed |= other.fixed & bit;
         }
 
-        Mask { mask }
+        difference
     }
 }
 
-impl From<&str> for Mask {
-    fn from(string: &str) -> Self {
-        let mut mask = [Bit::Zero; 36];
-        let mut ugh = string.chars().map(Bit::from).rev();
+// Memory for the version 2 decoder. Every write is kept as a pattern, and
+// later writes carve their addresses out of earlier ones so that the patterns
+// never overlap and memory can be summed without listing every address.
+#[derive(Debug, Default)]
+struct FloatingMemory {
+    writes: Vec<(Pattern, u64)>,
+}
+
+impl FloatingMemory {
+    fn write(&mut self, pattern: Pattern, value: u64) {
+        let mut writes = Vec::with_capacity(self.writes.len() + 1);
 
-        for bit in mask.iter_mut() {
-            *bit = ugh.next().unwrap();
+        for (old, old_value) in self.writes.drain(..) {
+            writes.extend(old.subtract(&pattern).into_iter().map(|p| (p, old_value)));
         }
 
-        Mask { mask }
+        writes.push((pattern, value));
+        self.writes = writes;
+    }
+
+    // The patterns are disjoint, so this is at most 2^64 addresses times a
+    // u64 value and always fits
+    fn sum(&self) -> u128 {
+        self.writes
+            .iter()
+            .map(|&(pattern, value)| pattern.len() * value as u128)
+            .sum()
     }
 }
 
-impl From<Mask> for usize {
-    fn from(mask: Mask) -> Self {
-        let mut total = 0;
-        let mut multiplier = 1;
+#[derive(Debug, PartialEq, Eq)]
+enum ParseErrorKind {
+    MissingEquals,
+    UnknownInstruction(String),
+    InvalidAddress(String),
+    InvalidValue(String),
+    InvalidMask(MaskError),
+}
 
-        for i in 0..36 {
-            match mask.mask[i] {
-                Bit::Zero => (),
-                Bit::One => total += multiplier,
-                Bit::Copy => panic!(),
-            }
+#[derive(Debug, PartialEq, Eq)]
+struct ParseError {
+    line: usize,
+    kind: ParseErrorKind,
+}
 
-            multiplier <<= 1;
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ParseErrorKind::*;
+
+        write!(f, "line {}: ", self.line)?;
+
+        match &self.kind {
+            MissingEquals => write!(f, "expected `<target> = <value>`"),
+            UnknownInstruction(target) => write!(f, "unknown instruction: {}", target),
+            InvalidAddress(address) => write!(f, "invalid address: {}", address),
+            InvalidValue(value) => write!(f, "invalid value: {}", value),
+            InvalidMask(err) => write!(f, "{}", err),
         }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum DockingInstruction {
+    SetMask(Mask),
+    Write { addr: u64, value: u64 },
+}
 
-        total
+impl DockingInstruction {
+    fn parse(line: &str, width: u32) -> Result<Self, ParseErrorKind> {
+        use ParseErrorKind::*;
+
+        let mut split = line.splitn(2, " = ");
+        let target = split.next().unwrap().trim();
+        let value = split.next().ok_or(MissingEquals)?.trim();
+
+        if target == "mask" {
+            return Mask::parse(value, width)
+                .map(DockingInstruction::SetMask)
+                .map_err(InvalidMask);
+        }
+
+        let addr = match target.strip_prefix("mem[") {
+            Some(rest) => rest
+                .strip_suffix(']')
+                .ok_or_else(|| InvalidAddress(target.to_string()))?,
+            None => return Err(UnknownInstruction(target.to_string())),
+        };
+
+        Ok(DockingInstruction::Write {
+            addr: addr.parse().map_err(|_| InvalidAddress(addr.to_string()))?,
+            value: value.parse().map_err(|_| InvalidValue(value.to_string()))?,
+        })
     }
 }
 
-impl fmt::Display for Mask {
+impl fmt::Display for DockingInstruction {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let ugh: String = self.mask.iter().map(|&bit| char::from(bit)).collect();
+        match self {
+            DockingInstruction::SetMask(mask) => write!(f, "mask = {}", mask.bits()),
+            DockingInstruction::Write { addr, value } => write!(f, "mem[{}] = {}", addr, value),
+        }
+    }
+}
 
-        write!(f, "M({})", ugh)
+fn parse_program(input: &str, width: u32) -> Result<Vec<DockingInstruction>, ParseError> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            DockingInstruction::parse(line, width).map_err(|kind| ParseError { line: i + 1, kind })
+        })
+        .collect()
+}
+
+// A decoder chip. The chips only differ in what the mask does to a write.
+trait Chip {
+    fn set_mask(&mut self, mask: Mask);
+    fn write(&mut self, addr: u64, value: u64);
+    fn sum(&self) -> u128;
+
+    // return type: [(addresses, value)] for every non-zero value, sorted by
+    // address
+    fn dump(&self) -> Vec<(Pattern, u64)>;
+
+    fn run(&mut self, program: &[DockingInstruction]) {
+        for instruction in program {
+            match *instruction {
+                DockingInstruction::SetMask(mask) => self.set_mask(mask),
+                DockingInstruction::Write { addr, value } => self.write(addr, value),
+            }
+        }
     }
 }
 
-impl Mask {
-    fn or(&self, other: &Self) -> Self {
-        let mut mask = [Bit::Zero; 36];
-        for (i, bit) in mask.iter_mut().enumerate() {
-            *bit = self.mask[i].or(&other.mask[i]);
+// Version 1: the mask applies to values
+struct ValueChip {
+    mask: Mask,
+    memory: HashMap<u64, u64>,
+}
+
+impl ValueChip {
+    fn new(width: u32) -> Self {
+        ValueChip {
+            mask: Mask::identity(width),
+            memory: Default::default(),
         }
+    }
+}
+
+impl Chip for ValueChip {
+    fn set_mask(&mut self, mask: Mask) {
+        self.mask = mask;
+    }
+
+    fn write(&mut self, addr: u64, value: u64) {
+        self.memory.insert(addr, self.mask.apply_to_value(value));
+    }
+
+    fn sum(&self) -> u128 {
+        self.memory.values().map(|&value| value as u128).sum()
+    }
+
+    fn dump(&self) -> Vec<(Pattern, u64)> {
+        let mut dump: Vec<(Pattern, u64)> = self
+            .memory
+            .iter()
+            .filter(|&(_, &value)| value != 0)
+            .map(|(&addr, &value)| {
+                let pattern = Pattern {
+                    fixed: addr,
+                    floating: 0,
+                };
+                (pattern, value)
+            })
+            .collect();
 
-        Self { mask }
+        dump.sort_unstable_by_key(|(pattern, _)| pattern.fixed);
+        dump
     }
+}
 
-    fn or2(&self, other: &Self) -> Self {
-        let mut mask = [Bit::Zero; 36];
-        for (i, bit) in mask.iter_mut().enumerate() {
-            *bit = self.mask[i].or2(&other.mask[i]);
+// Version 2: the mask applies to addresses
+struct AddressChip {
+    mask: Mask,
+    memory: FloatingMemory,
+}
+
+impl AddressChip {
+    fn new(width: u32) -> Self {
+        AddressChip {
+            mask: Mask {
+                ones: 0,
+                floating: 0,
+                width,
+            },
+            memory: Default::default(),
         }
+    }
+}
 
-        Self { mask }
+impl Chip for AddressChip {
+    fn set_mask(&mut self, mask: Mask) {
+        self.mask = mask;
+    }
+
+    fn write(&mut self, addr: u64, value: u64) {
+        self.memory.write(self.mask.apply_to_address(addr), value);
+    }
+
+    fn sum(&self) -> u128 {
+        self.memory.sum()
+    }
+
+    fn dump(&self) -> Vec<(Pattern, u64)> {
+        let mut dump: Vec<(Pattern, u64)> = self
+            .memory
+            .writes
+            .iter()
+            .filter(|&&(_, value)| value != 0)
+            .copied()
+            .collect();
+
+        dump.sort_unstable_by_key(|(pattern, _)| (pattern.fixed, pattern.floating));
+        dump
     }
 }
 
-fn main() {
-    part1();
-    part2();
+// Concrete addresses are printed in decimal, floating ones as the ternary
+// pattern they cover
+fn print_dump(chip: &dyn Chip, width: u32) {
+    for (pattern, value) in chip.dump() {
+        if pattern.floating == 0 {
+            println!("mem[{}] = {}", pattern.fixed, value);
+        } else {
+            let mask = Mask {
+                ones: pattern.fixed,
+                floating: pattern.floating,
+                width,
+            };
+
+            println!(
+                "mem[{}] = {} ({} addresses)",
+                mask.bits(),
+                value,
+                pattern.len()
+            );
+        }
+    }
 }
 
-fn part1() {
-    let v: Vec<(&str, &str)> = INPUT
-        .trim()
-        .lines()
-        .map(|line| line.split(" = "))
-        .map(|mut split| (split.next().unwrap(), split.next().unwrap()))
-        .collect();
-
-    let mask = [Bit::Zero; 36];
-    let mut mask = Mask { mask };
-    let mut memory = [mask; 65_536];
-
-    for (left, right) in v.iter() {
-        if left.starts_with("mask") {
-            mask = Mask::from(*right);
-        } else if left.starts_with("mem") {
-            let bounds = (left.find('[').unwrap() + 1, left.find(']').unwrap());
-            let address = &left[bounds.0..bounds.1];
-            let address: usize = address.parse().unwrap();
-
-            let value: usize = right.parse().unwrap();
-            let value_mask = Mask::from(value);
-            let new_value = value_mask.or(&mask);
-
-            memory[address] = new_value;
+// usage: day-14 [--width N] [--dump VERSION]
+fn main() {
+    let mut width = DEFAULT_WIDTH;
+    let mut dump = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--width" => match args.next().map(|width| width.parse()) {
+                Some(Ok(value)) => width = value,
+                _ => usage(),
+            },
+            "--dump" => match args.next().as_deref() {
+                Some("1") => dump = Some(1),
+                Some("2") => dump = Some(2),
+                _ => usage(),
+            },
+            _ => usage(),
         }
     }
 
-    let sum: usize = memory.iter().map(|mask| usize::from(*mask)).sum();
-    println!("part1 = {}", sum);
+    let program = match parse_program(INPUT, width) {
+        Ok(program) => program,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    match dump {
+        Some(1) => print_dump(&run(ValueChip::new(width), &program), width),
+        Some(_) => print_dump(&run(AddressChip::new(width), &program), width),
+        None => {
+            part1(&program, width);
+            part2(&program, width);
+        }
+    }
 }
 
-fn part2() {
-    let v: Vec<(&str, &str)> = INPUT
-        .trim()
-        .lines()
-        .map(|line| line.split(" = "))
-        .map(|mut split| (split.next().unwrap(), split.next().unwrap()))
-        .collect();
-
-    let mask = [Bit::Zero; 36];
-    let mut mask = Mask { mask };
-    let mut memory: HashMap<usize, Mask> = Default::default();
-
-    for (left, right) in v.iter() {
-        if left.starts_with("mask") {
-            mask = Mask::from(*right);
-        } else if left.starts_with("mem") {
-            let bounds = (left.find('[').unwrap() + 1, left.find(']').unwrap());
-            let address = &left[bounds.0..bounds.1];
-            let address: usize = address.parse().unwrap();
-            let address_mask = Mask::from(address);
-
-            let value: usize = right.parse().unwrap();
-            let value_mask = Mask::from(value);
-
-            let mut done: Vec<Mask> = vec![];
-            let mut masks: VecDeque<Mask> = Default::default();
-            let initial_address_mask = address_mask.or2(&mask);
-            masks.push_back(initial_address_mask);
-
-            while !masks.is_empty() {
-                let mut mask = masks.pop_front().unwrap();
-                let copy_index = match mask.mask.iter().position(|&bit| bit == Bit::Copy) {
-                    Some(i) => i,
-                    None => {
-                        done.push(mask);
-                        continue;
-                    }
-                };
+fn usage() -> ! {
+    eprintln!("usage: day-14 [--width N] [--dump VERSION]");
+    std::process::exit(1);
+}
 
-                let mut one_mask = mask;
-                one_mask.mask[copy_index] = Bit::One;
-                masks.push_back(one_mask);
+fn run<C: Chip>(mut chip: C, program: &[DockingInstruction]) -> C {
+    chip.run(program);
+    chip
+}
 
-                mask.mask[copy_index] = Bit::Zero;
-                masks.push_back(mask);
-            }
+fn part1(program: &[DockingInstruction], width: u32) {
+    println!("part1 = {}", run(ValueChip::new(width), program).sum());
+}
 
-            for mask in done {
-                let address = usize::from(mask);
-                memory.insert(address, value_mask);
-            }
+fn part2(program: &[DockingInstruction], width: u32) {
+    println!("part2 = {}", run(AddressChip::new(width), program).sum());
+}
+
+mod test {
+    #[allow(unused)]
+    use super::*;
+
+    #[allow(unused)]
+    fn sum(chip: impl Chip, input: &str, width: u32) -> u128 {
+        run(chip, &parse_program(input, width).unwrap()).sum()
+    }
+
+    #[allow(unused)]
+    fn addresses(pattern: Pattern) -> Vec<u64> {
+        let mut addresses: Vec<u64> = (0..=pattern.floating)
+            .filter(|&subset| subset & !pattern.floating == 0)
+            .map(|subset| pattern.fixed | subset)
+            .collect();
+        addresses.sort_unstable();
+        addresses
+    }
+
+    #[test]
+    fn subtract_test() {
+        let pattern = |mask: &str| Mask::parse(mask, 6).unwrap().apply_to_address(0);
+
+        let a = pattern("X1X0XX");
+        let cases = [pattern("0XX1X0"), pattern("X11XX1"), pattern("000000"), a];
+
+        for b in cases.iter() {
+            let difference = a.subtract(b);
+
+            let mut expected: Vec<u64> = addresses(a)
+                .into_iter()
+                .filter(|address| !addresses(*b).contains(address))
+                .collect();
+            expected.sort_unstable();
+
+            let mut actual: Vec<u64> = difference.iter().flat_map(|&p| addresses(p)).collect();
+            actual.sort_unstable();
+
+            assert_eq!(expected, actual);
+            assert_eq!(
+                actual.len() as u128,
+                difference.iter().map(Pattern::len).sum::<u128>()
+            );
         }
     }
 
-    let sum: usize = memory
-        .iter()
-        .map(|(_address, mask)| usize::from(*mask))
-        .sum();
-    println!("part2 = {}", sum);
+    #[test]
+    fn mask_test() {
+        let mask = Mask::parse("XXXXXXXXXXXXXXXXXXXXXXXXXXXXX1XXXX0X", 36).unwrap();
+        assert_eq!(73, mask.apply_to_value(11));
+        assert_eq!(101, mask.apply_to_value(101));
+        assert_eq!(64, mask.apply_to_value(0));
+        assert_eq!("M(XXXXXXXXXXXXXXXXXXXXXXXXXXXXX1XXXX0X)", mask.to_string());
+
+        let mask = Mask::parse("X1001X", 6).unwrap();
+        assert_eq!(vec![26, 27, 58, 59], addresses(mask.apply_to_address(42)));
+
+        assert_eq!(
+            Err(MaskError::Length {
+                expected: 36,
+                found: 6
+            }),
+            Mask::parse("X1001X", 36)
+        );
+        assert_eq!(Err(MaskError::InvalidBit('2')), Mask::parse("X1002X", 6));
+        assert_eq!(Err(MaskError::Width(65)), Mask::parse("", 65));
+    }
+
+    #[test]
+    fn part_test() {
+        let input = "mask = XXXXXXXXXXXXXXXXXXXXXXXXXXXXX1XXXX0X\n\
+                     mem[8] = 11\n\
+                     mem[7] = 101\n\
+                     mem[8] = 0\n";
+        assert_eq!(165, sum(ValueChip::new(36), input, 36));
+        assert_eq!(
+            208,
+            sum(AddressChip::new(36), include_str!("../test-input.txt"), 36)
+        );
+
+        let input = "mask = 1X0X\nmem[1] = 3\nmask = X1X1\nmem[0] = 2\n";
+        assert_eq!(16, sum(ValueChip::new(4), input, 4));
+        assert_eq!(17, sum(AddressChip::new(4), input, 4));
+    }
+
+    #[test]
+    fn many_floating_bits_test() {
+        let input = format!(
+            "mask = {}{}\nmem[0] = 1\nmask = 1{}{}\nmem[0] = 2\n",
+            "X".repeat(40),
+            "0".repeat(8),
+            "X".repeat(39),
+            "0".repeat(8)
+        );
+        assert_eq!(3 << 39, sum(AddressChip::new(48), &input, 48));
+    }
+
+    #[test]
+    fn full_width_test() {
+        let input = format!("mask = {}\nmem[0] = {}\n", "X".repeat(64), u64::MAX);
+        assert_eq!(
+            (u64::MAX as u128) << 64,
+            sum(AddressChip::new(64), &input, 64)
+        );
+
+        let input = format!("mask = {}\nmem[0] = 68719476735\n", "X".repeat(36));
+        assert_eq!(68719476735 << 36, sum(AddressChip::new(36), &input, 36));
+        assert_eq!(68719476735, sum(ValueChip::new(36), &input, 36));
+    }
+
+    #[test]
+    fn parse_program_test() {
+        let input = "mask = X1001X\nmem[42] = 100\n\nmem[7] = 0\n";
+        let program = parse_program(input, 6).unwrap();
+        assert_eq!(
+            DockingInstruction::Write {
+                addr: 42,
+                value: 100
+            },
+            program[1]
+        );
+        assert_eq!(3, program.len());
+        assert_eq!(
+            "mask = X1001X\nmem[42] = 100\nmem[7] = 0",
+            program
+                .iter()
+                .map(DockingInstruction::to_string)
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+
+        let errors = vec![
+            ("mem[1] 5", ParseErrorKind::MissingEquals),
+            (
+                "reg[1] = 5",
+                ParseErrorKind::UnknownInstruction("reg[1]".to_string()),
+            ),
+            (
+                "mem[x] = 5",
+                ParseErrorKind::InvalidAddress("x".to_string()),
+            ),
+            (
+                "mem[1 = 5",
+                ParseErrorKind::InvalidAddress("mem[1".to_string()),
+            ),
+            (
+                "mem[1] = -5",
+                ParseErrorKind::InvalidValue("-5".to_string()),
+            ),
+            (
+                "mask = X1002X",
+                ParseErrorKind::InvalidMask(MaskError::InvalidBit('2')),
+            ),
+        ];
+
+        for (line, kind) in errors {
+            let input = format!("mask = X1001X\n{}\n", line);
+            assert_eq!(Err(ParseError { line: 2, kind }), parse_program(&input, 6));
+        }
+    }
+
+    #[test]
+    fn dump_test() {
+        let program = parse_program(
+            "mask = 0X0X\nmem[1] = 3\nmem[2] = 0\nmask = 0001\nmem[0] = 7\n",
+            4,
+        )
+        .unwrap();
+
+        let dump = run(ValueChip::new(4), &program).dump();
+        let addresses: Vec<u64> = dump.iter().map(|(pattern, _)| pattern.fixed).collect();
+        assert_eq!(vec![0, 1], addresses);
+
+        let dump = run(AddressChip::new(4), &program).dump();
+        let total: u128 = dump.iter().map(|(pattern, _)| pattern.len()).sum();
+        assert_eq!(4, total);
+        assert!(dump.iter().all(|&(_, value)| value != 0));
+    }
 }